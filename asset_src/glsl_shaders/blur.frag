@@ -0,0 +1,23 @@
+#version 450 core
+
+layout(location=0) in vec2 v_uv;
+layout(location=0) out vec4 f_color;
+
+layout(set=0, binding=0) uniform texture2D t_source;
+layout(set=0, binding=1) uniform sampler s_linear;
+#include "post_uniforms.glsl"
+
+//9 tap gaussian in one direction, in 5 samples by letting the linear filtering blend neighbouring texels
+const float offsets[3] = float[](0.0, 1.3846153846, 3.2307692308);
+const float weights[3] = float[](0.2270270270, 0.3162162162, 0.0702702703);
+
+void main() {
+	vec2 stride = direction * texel_size;
+	vec3 color = texture(sampler2D(t_source, s_linear), v_uv).rgb * weights[0];
+	for (int i = 1; i < 3; i++) {
+		color += texture(sampler2D(t_source, s_linear), v_uv + stride * offsets[i]).rgb * weights[i];
+		color += texture(sampler2D(t_source, s_linear), v_uv - stride * offsets[i]).rgb * weights[i];
+	}
+
+	f_color = vec4(color, 1.0);
+}
@@ -0,0 +1,20 @@
+#version 450 core
+
+layout(location=0) in vec2 v_uv;
+layout(location=0) out vec4 f_color;
+
+layout(set=0, binding=0) uniform texture2D t_source;
+layout(set=0, binding=1) uniform sampler s_linear;
+#include "post_uniforms.glsl"
+
+//keeps only what's brighter than the threshold, with a soft knee so the cutoff doesn't make hard edges
+void main() {
+	vec3 color = texture(sampler2D(t_source, s_linear), v_uv).rgb;
+	float brightness = max(color.r, max(color.g, color.b));
+
+	float soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+	soft = soft * soft / (4.0 * knee + 0.00001);
+	float contribution = max(soft, brightness - threshold) / max(brightness, 0.00001);
+
+	f_color = vec4(color * contribution, 1.0);
+}
@@ -0,0 +1,52 @@
+#version 450 core
+
+layout(location=0) in vec2 v_uv;
+layout(location=0) out vec4 f_color;
+
+layout(set=0, binding=0) uniform texture2D t_source;
+layout(set=0, binding=1) uniform sampler s_linear;
+#include "post_uniforms.glsl"
+layout(set=0, binding=3) uniform texture2D t_bloom;
+
+//Narkowicz's fit of the ACES filmic curve
+vec3 aces(vec3 x) {
+	return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main() {
+	vec2 uv = v_uv;
+	vec2 centered = uv * 2.0 - 1.0;
+
+	if (crt == 1) {
+		//bulge out like the glass of an old screen. the corners fall off the edge
+		centered *= 1.0 + curvature * dot(centered, centered);
+		uv = centered * 0.5 + 0.5;
+		if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+			f_color = vec4(0.0, 0.0, 0.0, 1.0);
+			return;
+		}
+	}
+
+	vec3 scene = texture(sampler2D(t_source, s_linear), uv).rgb;
+	vec3 bloom = texture(sampler2D(t_bloom, s_linear), uv).rgb;
+	vec3 color = (scene + bloom * bloom_strength) * exposure;
+
+	if (crt == 1) {
+		float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+		color = mix(color, luminance * phosphor, monochrome);
+
+		float row = uv.y / texel_size.y / scanline_spacing;
+		color *= 1.0 - scanlines * (0.5 + 0.5 * cos(row * 6.28318530718));
+		color *= clamp(1.0 - vignette * dot(centered, centered) * 0.5, 0.0, 1.0);
+		color *= flicker;
+	}
+
+	if (tonemap == 1) {
+		color = color / (color + 1.0);
+	} else if (tonemap == 2) {
+		color = aces(color);
+	}
+
+	//the swap chain is srgb, so it does the gamma
+	f_color = vec4(color, 1.0);
+}
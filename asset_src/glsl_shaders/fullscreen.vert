@@ -0,0 +1,11 @@
+#version 450 core
+
+layout(location=0) out vec2 v_uv;
+
+//one big triangle that covers the whole screen, no vertex buffer needed
+void main() {
+	vec2 corner = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+	v_uv = vec2(corner.x, 1.0 - corner.y);
+
+	gl_Position = vec4(corner * 2.0 - 1.0, 0.0, 1.0);
+}
@@ -1,8 +1,27 @@
 #version 450 core
 
 layout(location=0) in vec3 v_color;
+layout(location=1) in vec3 v_beam;
 layout(location=0) out vec4 f_color;
 
+layout(set=0,binding=0)
+uniform Uniforms {
+	mat4 pixel_to_ndc;
+	mat4 view;
+	//x: how sharply the beam fades towards the edges of a line (0 for flat lines)
+	//y: how far from the ends of a line the beam lingers, in world units (0 for not at all)
+	//z: how much brighter the ends get where it lingers
+	vec4 beam;
+};
+
 void main() {
-	f_color = vec4(v_color, 1.0);
-}
\ No newline at end of file
+	float intensity = 1.0;
+
+	//a vector display's beam is brightest in the middle, and lingers at the ends of each stroke
+	intensity *= exp(-beam.x * v_beam.x * v_beam.x);
+	if (beam.y > 0.0) {
+		intensity *= 1.0 + beam.z * exp(-min(v_beam.y, v_beam.z) / beam.y);
+	}
+
+	f_color = vec4(v_color * intensity, 1.0);
+}
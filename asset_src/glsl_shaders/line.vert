@@ -2,16 +2,21 @@
 
 layout(location=0) in vec2 a_pos;
 layout(location=1) in vec3 a_color;
+layout(location=2) in vec3 a_beam;
 
 layout(location=0) out vec3 v_color;
+layout(location=1) out vec3 v_beam;
 
 layout(set=0,binding=0)
 uniform Uniforms {
 	mat4 pixel_to_ndc;
+	mat4 view;
+	vec4 beam;
 };
 
 void main() {
 	v_color = a_color;
+	v_beam = a_beam;
 	
-	gl_Position = pixel_to_ndc * vec4(a_pos, 0.0, 1.0); //z = 0 for now (no projection matrix set up)
-}
\ No newline at end of file
+	gl_Position = pixel_to_ndc * view * vec4(a_pos, 0.0, 1.0); //z = 0 for now (no projection matrix set up)
+}
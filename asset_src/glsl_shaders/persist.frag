@@ -0,0 +1,17 @@
+#version 450 core
+
+layout(location=0) in vec2 v_uv;
+layout(location=0) out vec4 f_color;
+
+layout(set=0, binding=0) uniform texture2D t_source;
+layout(set=0, binding=1) uniform sampler s_linear;
+#include "post_uniforms.glsl"
+layout(set=0, binding=3) uniform texture2D t_history;
+
+//phosphor keeps glowing after the beam moves on. whatever's brighter, this frame or what's left of the last ones
+void main() {
+	vec3 now = texture(sampler2D(t_source, s_linear), v_uv).rgb;
+	vec3 before = texture(sampler2D(t_history, s_linear), v_uv).rgb * persistence;
+
+	f_color = vec4(max(now, before), 1.0);
+}
@@ -0,0 +1,20 @@
+//shared by the post process passes, matches `PostUniforms` in post_process.rs
+layout(set=0, binding=2)
+uniform PostUniforms {
+	vec2 texel_size;
+	vec2 direction;
+	float threshold;
+	float knee;
+	float bloom_strength;
+	float exposure;
+	uint tonemap;
+	float persistence;
+	float flicker;
+	float scanlines;
+	float scanline_spacing;
+	float curvature;
+	float vignette;
+	float monochrome;
+	vec3 phosphor;
+	uint crt;
+};
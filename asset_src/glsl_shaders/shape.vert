@@ -0,0 +1,32 @@
+#version 450 core
+
+//one shape's mesh, in its own local space
+layout(location=0) in vec2 a_pos;
+layout(location=1) in vec3 a_color;
+layout(location=2) in vec3 a_beam;
+
+//where this copy of it goes
+layout(location=3) in vec2 i_x_axis;
+layout(location=4) in vec2 i_y_axis;
+layout(location=5) in vec2 i_position;
+layout(location=6) in float i_scale;
+layout(location=7) in vec3 i_tint;
+
+layout(location=0) out vec3 v_color;
+layout(location=1) out vec3 v_beam;
+
+layout(set=0,binding=0)
+uniform Uniforms {
+	mat4 pixel_to_ndc;
+	mat4 view;
+	vec4 beam;
+};
+
+void main() {
+	v_color = a_color * i_tint;
+	//distances along the line get scaled with it, the side it's on doesn't
+	v_beam = vec3(a_beam.x, a_beam.yz * i_scale);
+
+	vec2 world = i_x_axis * a_pos.x + i_y_axis * a_pos.y + i_position;
+	gl_Position = pixel_to_ndc * view * vec4(world, 0.0, 1.0);
+}
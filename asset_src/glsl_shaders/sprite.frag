@@ -0,0 +1,12 @@
+#version 450 core
+
+layout(location=0) in vec2 v_uv;
+layout(location=1) in vec4 v_tint;
+layout(location=0) out vec4 f_color;
+
+layout(set=1,binding=0) uniform texture2D t_sprite;
+layout(set=1,binding=1) uniform sampler s_sprite;
+
+void main() {
+	f_color = texture(sampler2D(t_sprite, s_sprite), v_uv) * v_tint;
+}
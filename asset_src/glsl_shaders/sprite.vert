@@ -0,0 +1,32 @@
+#version 450 core
+
+//one sprite per instance, no vertex buffer. the corners come from the vertex index
+layout(location=0) in vec2 i_x_axis;
+layout(location=1) in vec2 i_y_axis;
+layout(location=2) in vec2 i_position;
+layout(location=3) in vec2 i_uv_min;
+layout(location=4) in vec2 i_uv_max;
+layout(location=5) in vec4 i_tint;
+
+layout(location=0) out vec2 v_uv;
+layout(location=1) out vec4 v_tint;
+
+layout(set=0,binding=0)
+uniform Uniforms {
+	mat4 pixel_to_ndc;
+	mat4 view;
+	vec4 beam;
+};
+
+const vec2 CORNERS[6] = vec2[](vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(1.0, 1.0), vec2(0.0, 0.0), vec2(1.0, 1.0), vec2(0.0, 1.0));
+
+void main() {
+	vec2 corner = CORNERS[gl_VertexIndex];
+	v_uv = mix(i_uv_min, i_uv_max, corner);
+	v_tint = i_tint;
+
+	//the axes are as long as the sprite is big, and it's centered on its position
+	vec2 local = corner - vec2(0.5);
+	vec2 world = i_x_axis * local.x + i_y_axis * local.y + i_position;
+	gl_Position = pixel_to_ndc * view * vec4(world, 0.0, 1.0);
+}
@@ -1,56 +1,564 @@
+//! Builds everything in `asset_src` into an output directory next to the executable (`target/<profile>/assets`),
+//! keeping the same layout. Each kind of file has its own processor: shaders get compiled, sprites get packed into an
+//! atlas, RON files get checked, and everything else gets copied. Outputs remember a hash of what they were made from,
+//! so unchanged files get skipped.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::env;
+use std::fmt::Write;
 use std::fs::{self};
 use std::path::Path;
+use std::path::PathBuf;
 
 use anyhow::*;
+use image::GenericImage;
+use image::RgbaImage;
+use ron::Value;
+
+#[path = "src/util/hash.rs"]
+mod hash;
 
-static GLSL_PREFIX: &str = "./asset_src/glsl_shaders";
-static SPV_PREFIX: &str = "./assets/compiled_shaders";
+static SRC: &str = "./asset_src";
+/// Shaders in here get compiled into `SPV_DIR`.
+static GLSL_DIR: &str = "glsl_shaders";
+/// Shaders with this extension only get `#include`d into other shaders, they aren't compiled by themselves.
+static GLSL_INCLUDE_EXTENSION: &str = "glsl";
+static SPV_DIR: &str = "compiled_shaders";
+/// Images in here get packed into the `sprites` atlas, in `ATLAS_DIR`.
+static SPRITE_DIR: &str = "sprites";
+static ATLAS_DIR: &str = "atlases";
+static ATLAS_NAME: &str = "sprites";
+/// Where maps look up the kinds of enemy they use.
+static ENEMIES_FILE: &str = "world/enemies.ron";
+/// Remembers what each output was made from, in the output directory.
+static HASH_FILE: &str = ".asset_hashes";
+
+/// Atlas pages are square, this many pixels on a side.
+const PAGE_SIZE: u32 = 1024;
+/// Empty space around each sprite on a page, so filtering doesn't pick up its neighbours.
+const PADDING: u32 = 2;
+/// How far each sprite's edge pixels get smeared out into its padding, so filtering right at the edge doesn't pick up
+/// the empty space either. Can't be more than `PADDING`.
+const EXTRUDE: u32 = 1;
 
 fn main() {
-	compile(Path::new(GLSL_PREFIX), &mut shaderc::Compiler::new().context("cant make shaderc").unwrap()).expect("problem walking file tree");
+	let out = output_dir();
+	println!("cargo:rerun-if-changed={}", SRC);
+	println!("cargo:rustc-env=ASSET_DIR={}", out.to_string_lossy());
+	build_assets(Path::new(SRC), &out).expect("problem building assets");
+}
+
+/// `OUT_DIR` is `target/<profile>/build/<this crate>-<hash>/out`, so this ends up as `target/<profile>/assets`.
+fn output_dir() -> PathBuf {
+	let out_dir = PathBuf::from(env::var("OUT_DIR").expect("no OUT_DIR"));
+	out_dir.ancestors().nth(3).expect("OUT_DIR isn't where it usually is").join("assets")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Processor {
+	/// GLSL, compiled into SPIR-V.
+	Shader,
+	/// GLSL for other shaders to include. Goes in every shader job, rather than getting one of its own.
+	ShaderInclude,
+	/// An image, packed into the atlas along with all the other sprites.
+	Sprite,
+	/// Checked that it parses (and, if it's a map, that it makes sense), then copied.
+	Ron,
+	/// Copied as is.
+	Copy,
+}
+
+impl Processor {
+	fn for_path(relative: &Path) -> Self {
+		let extension = relative.extension().and_then(|ext| ext.to_str());
+		if relative.starts_with(GLSL_DIR) && extension == Some(GLSL_INCLUDE_EXTENSION) {
+			Processor::ShaderInclude
+		} else if relative.starts_with(GLSL_DIR) {
+			Processor::Shader
+		} else if relative.starts_with(SPRITE_DIR) && extension == Some("png") {
+			Processor::Sprite
+		} else if extension == Some("ron") {
+			Processor::Ron
+		} else {
+			Processor::Copy
+		}
+	}
+}
+
+/// Some inputs, and what to do with them. Most jobs are one file, but all the sprites go in one job, and shader jobs
+/// have every include after the shader itself.
+struct Job {
+	processor: Processor,
+	inputs: Vec<PathBuf>,
+}
+
+/// What an earlier build made, and what from.
+#[derive(Clone)]
+struct Record {
+	hash: u64,
+	outputs: Vec<String>,
 }
 
-fn compile(path: &Path, compiler: &mut shaderc::Compiler) -> Result<()> {
+fn build_assets(src: &Path, out: &Path) -> Result<()> {
+	let mut files = Vec::new();
+	find_files(src, src, &mut files)?;
+	files.sort();
+
+	let mut jobs: BTreeMap<String, Job> = BTreeMap::new();
+	let mut includes = Vec::new();
+	for relative in files {
+		let processor = Processor::for_path(&relative);
+		if processor == Processor::ShaderInclude {
+			includes.push(relative);
+			continue;
+		}
+		let key = if processor == Processor::Sprite { SPRITE_DIR.to_string() } else { slashed(&relative) };
+		jobs.entry(key).or_insert_with(|| Job { processor, inputs: Vec::new() }).inputs.push(relative);
+	}
+	//the game always loads the atlas, so there needs to be one even with no sprites to go in it
+	jobs.entry(SPRITE_DIR.to_string()).or_insert_with(|| Job { processor: Processor::Sprite, inputs: Vec::new() });
+	//so they get hashed along with the shader, and changing an include rebuilds everything that might use it
+	for job in jobs.values_mut().filter(|job| job.processor == Processor::Shader) {
+		job.inputs.extend(includes.iter().cloned());
+	}
+
+	//maps point at other files, which could've changed even if the map didn't, so these always get checked
+	let context = MapContext::new(src, &jobs)?;
+	for (key, job) in &jobs {
+		if job.processor == Processor::Ron {
+			validate_ron(&src.join(key), &context).with_context(|| format!("problem checking {}", key))?;
+		}
+	}
+
+	//changing how things get built should build them all again
+	let salt = hash::fnv1a(include_bytes!("build.rs"));
+	let hash_file = out.join(HASH_FILE);
+	let old_records = read_records(&hash_file);
+	let mut records: BTreeMap<String, Record> = BTreeMap::new();
+	let mut shader_compiler = None;
+
+	for (key, job) in &jobs {
+		let mut hashed = salt.to_le_bytes().to_vec();
+		for input in &job.inputs {
+			hashed.extend_from_slice(slashed(input).as_bytes());
+			hashed.extend_from_slice(&hash::fnv1a(&fs::read(src.join(input))?).to_le_bytes());
+		}
+		let hash = hash::fnv1a(&hashed);
+
+		if let Some(old) = old_records.get(key) {
+			if old.hash == hash && old.outputs.iter().all(|output| out.join(output).exists()) {
+				records.insert(key.clone(), old.clone());
+				continue;
+			}
+			remove_outputs(out, &old.outputs);
+		}
+
+		let outputs = match job.processor {
+			Processor::Shader => {
+				let compiler = match &mut shader_compiler {
+					Some(compiler) => compiler,
+					None => shader_compiler.get_or_insert(shaderc::Compiler::new().context("cant make shaderc")?),
+				};
+				vec![compile_shader(src, &job.inputs[0], out, compiler)?]
+			},
+			Processor::ShaderInclude => unreachable!("includes don't get jobs of their own"),
+			Processor::Sprite => pack_atlas(src, &job.inputs, out)?,
+			Processor::Ron | Processor::Copy => {
+				let output = out.join(&job.inputs[0]);
+				fs::create_dir_all(output.parent().unwrap())?;
+				fs::copy(src.join(&job.inputs[0]), &output).with_context(|| format!("problem copying {}", key))?;
+				vec![key.clone()]
+			},
+		};
+		records.insert(key.clone(), Record { hash, outputs });
+	}
+
+	//and clean up after anything that got deleted from asset_src
+	for (key, old) in &old_records {
+		if !records.contains_key(key) {
+			remove_outputs(out, &old.outputs);
+		}
+	}
+
+	write_records(&hash_file, &records)
+}
+
+/// Every file under `path`, relative to `root`.
+fn find_files(root: &Path, path: &Path, found: &mut Vec<PathBuf>) -> Result<()> {
 	for entry in fs::read_dir(path)? {
 		let entry = entry?;
-		let meta = entry.metadata()?;
-		if meta.is_dir() {
-			compile(&entry.path(), compiler)?;
+		if entry.metadata()?.is_dir() {
+			find_files(root, &entry.path(), found)?;
 		} else {
-			let result: Result<()> = {
-				//tell cargo to rebuild shaders if *any* shader has changed (kinda crappy)
-				println!("cargo:rerun-if-changed={}", entry.path().as_os_str().to_str().unwrap());
-
-				//read the shader source code
-				let src = fs::read_to_string(entry.path())?;
-
-				//guess the stage based off the file extension
-				let kind = match entry.path().extension().map(|s| s.to_str().unwrap()) {
-					Some("vert") => shaderc::ShaderKind::Vertex,
-					Some("frag") => shaderc::ShaderKind::Fragment,
-					Some("comp") => shaderc::ShaderKind::Compute,
-					Some(other) => bail!("unknown file extension '{}'", other),
-					None => bail!("no file extension"),
-				};
+			found.push(entry.path().strip_prefix(root)?.to_path_buf());
+		}
+	}
+	Ok(())
+}
+
+/// Forward slashes on every platform, for keys and for paths the game asks for.
+fn slashed(path: &Path) -> String {
+	path.to_string_lossy().replace('\\', "/")
+}
+
+/// One line per job: the hash, the key, then everything it made, separated by tabs.
+fn read_records(path: &Path) -> BTreeMap<String, Record> {
+	let file = fs::read_to_string(path).unwrap_or_default();
+	file.lines()
+		.filter_map(|line| {
+			let mut parts = line.split('\t');
+			let hash = u64::from_str_radix(parts.next()?, 16).ok()?;
+			let key = parts.next()?.to_string();
+			Some((key, Record { hash, outputs: parts.map(str::to_string).collect() }))
+		})
+		.collect()
+}
+
+fn write_records(path: &Path, records: &BTreeMap<String, Record>) -> Result<()> {
+	let mut file = String::new();
+	for (key, record) in records {
+		writeln!(file, "{:016x}\t{}\t{}", record.hash, key, record.outputs.join("\t"))?;
+	}
+	fs::create_dir_all(path.parent().unwrap())?;
+	fs::write(path, file).context("problem writing asset hashes")
+}
+
+fn remove_outputs(out: &Path, outputs: &[String]) {
+	for output in outputs {
+		let _ = fs::remove_file(out.join(output));
+	}
+}
+
+/// Returns the output, relative to `out`.
+fn compile_shader(src: &Path, relative: &Path, out: &Path, compiler: &mut shaderc::Compiler) -> Result<String> {
+	let path = src.join(relative);
+
+	//read the shader source code
+	let source = fs::read_to_string(&path)?;
+
+	//guess the stage based off the file extension
+	let kind = match relative.extension().map(|s| s.to_str().unwrap()) {
+		Some("vert") => shaderc::ShaderKind::Vertex,
+		Some("frag") => shaderc::ShaderKind::Fragment,
+		Some("comp") => shaderc::ShaderKind::Compute,
+		Some(other) => bail!("unknown file extension '{}'", other),
+		None => bail!("no file extension"),
+	};
+
+	//`#include "x"` is relative to the file it's in, `#include <x>` to `GLSL_DIR`
+	let mut options = shaderc::CompileOptions::new().context("cant make shaderc options")?;
+	let include_dir = src.join(GLSL_DIR);
+	options.set_include_callback(move |name, include_type, from, _depth| {
+		let dir = match include_type {
+			shaderc::IncludeType::Relative => Path::new(from).parent().map_or_else(|| include_dir.clone(), Path::to_path_buf),
+			shaderc::IncludeType::Standard => include_dir.clone(),
+		};
+		let include = dir.join(name);
+		fs::read_to_string(&include)
+			.map(|content| shaderc::ResolvedInclude { resolved_name: include.to_string_lossy().into_owned(), content })
+			.map_err(|e| format!("problem reading {}: {}", include.to_string_lossy(), e))
+	});
+
+	//compile the shader into spir-v with naga
+	let compiled = compiler
+		.compile_into_spirv(&source, kind, &path.to_string_lossy(), "main", Some(&options))
+		.with_context(|| format!("problem compiling {}", path.to_string_lossy()))?;
+
+	//choose the output path "glsl_shaders/pog.vert -> compiled_shaders/pog.vert.spv"
+	let mut output = Path::new(SPV_DIR).join(relative.strip_prefix(GLSL_DIR)?);
+	let mut out_ext = output.extension().unwrap().to_str().unwrap().to_string();
+	out_ext.push_str(".spv");
+	output.set_extension(out_ext);
+
+	//write the output
+	fs::create_dir_all(out.join(&output).parent().unwrap())?;
+	fs::write(out.join(&output), compiled.as_binary_u8())?;
+
+	Ok(slashed(&output))
+}
+
+/// What maps are allowed to refer to.
+struct MapContext<'a> {
+	src: &'a Path,
+	/// Names sprites in the atlas will have.
+	sprites: BTreeSet<String>,
+	enemy_kinds: BTreeSet<String>,
+}
 
-				//compile the shader into spir-v with naga
-				let compiled = compiler.compile_into_spirv(&src, kind, &entry.path().to_string_lossy(), "main", None)?;
+impl<'a> MapContext<'a> {
+	fn new(src: &'a Path, jobs: &BTreeMap<String, Job>) -> Result<Self> {
+		let sprites = jobs.get(SPRITE_DIR).map_or_else(BTreeSet::new, |job| job.inputs.iter().map(|input| sprite_name(input)).collect());
 
-				//choose the output path "./shader_glsl/pog.vert -> ./shader_spv/pog.vert.spv"
-				let mut out_path = Path::new(SPV_PREFIX).join(entry.path().strip_prefix(GLSL_PREFIX)?);
-				let mut out_ext = out_path.extension().unwrap().to_str().unwrap().to_string();
-				out_ext.push_str(".spv");
-				out_path.set_extension(out_ext);
+		let mut enemy_kinds = BTreeSet::new();
+		if src.join(ENEMIES_FILE).is_file() {
+			let file = fs::read_to_string(src.join(ENEMIES_FILE))?;
+			//it's a newtype around a map, so it comes out as a sequence with the map in it
+			let enemies: Value = ron::from_str(&file).with_context(|| format!("problem parsing {}", ENEMIES_FILE))?;
+			if let Value::Seq(items) = enemies {
+				for item in items {
+					if let Value::Map(kinds) = item {
+						enemy_kinds.extend(kinds.keys().filter_map(as_str).map(str::to_string));
+					}
+				}
+			}
+		}
+
+		Ok(MapContext { src, sprites, enemy_kinds })
+	}
+}
+
+/// Every RON file has to at least parse. Maps (anything in `world` with lines in it) get checked for the sort of
+/// mistakes that would otherwise only show up once the map is loaded, or drawn.
+fn validate_ron(path: &Path, context: &MapContext) -> Result<()> {
+	let file = fs::read_to_string(path)?;
+	let value: Value = ron::from_str(&file).context("doesn't parse")?;
+
+	let map = match &value {
+		Value::Map(map) if path.starts_with(context.src.join("world")) && field(map, "lines").is_some() => map,
+		_ => return Ok(()),
+	};
+
+	for (index, line) in seq(field(map, "lines")).iter().enumerate() {
+		check_polyline(line).with_context(|| format!("line {}", index))?;
+	}
 
-				//write the output
-				std::fs::write(out_path, compiled.as_binary_u8())?;
+	for (index, entity) in seq(field(map, "entities")).iter().enumerate() {
+		let entity = match entity {
+			Value::Map(entity) => entity,
+			_ => bail!("entity {} isn't a struct", index),
+		};
+		if let Some(shape) = field(entity, "shape").and_then(some) {
+			check_polyline(shape).with_context(|| format!("entity {}'s shape", index))?;
+		}
+		if let Some(Value::Map(brain)) = field(entity, "brain").and_then(some) {
+			let kind = field(brain, "kind").and_then(as_str).unwrap_or_default();
+			if !context.enemy_kinds.contains(kind) {
+				bail!("entity {} is a kind of enemy that isn't in {}: '{}'", index, ENEMIES_FILE, kind);
+			}
+		}
+	}
 
-				Ok(())
-			};
+	//either a cave added to the map, or the one an endless map streams in
+	let streaming_cave = match field(map, "streaming").and_then(some) {
+		Some(Value::Map(streaming)) => field(streaming, "cave"),
+		_ => None,
+	};
+	for cave in field(map, "cave").and_then(some).into_iter().chain(streaming_cave).filter_map(as_str) {
+		if !context.src.join(cave).is_file() {
+			bail!("cave params file '{}' doesn't exist", cave);
+		}
+	}
 
-			result.with_context(|| format!("problem compiling {}", entry.path().to_string_lossy()))?
+	for (index, decal) in seq(field(map, "decals")).iter().enumerate() {
+		let texture = match decal {
+			Value::Map(decal) => field(decal, "texture").and_then(as_str).unwrap_or_default(),
+			_ => bail!("decal {} isn't a struct", index),
+		};
+		if !context.sprites.contains(texture) && !context.src.join(texture).is_file() {
+			bail!("decal {} uses '{}', which isn't a sprite or an image", index, texture);
 		}
 	}
 
 	Ok(())
 }
+
+/// Polylines with fewer than two points can't be tessellated.
+fn check_polyline(line: &Value) -> Result<()> {
+	let line = match line {
+		Value::Map(line) => line,
+		_ => bail!("isn't a struct"),
+	};
+	let points = seq(field(line, "points")).len();
+	if points < 2 {
+		bail!("has {} points, it needs at least 2", points);
+	}
+	match field(line, "thickness") {
+		Some(Value::Number(thickness)) if thickness.into_f64() > 0.0 => Ok(()),
+		_ => bail!("needs a thickness above 0"),
+	}
+}
+
+fn field<'v>(map: &'v ron::Map, name: &str) -> Option<&'v Value> {
+	map.iter().find(|(key, _)| as_str(key) == Some(name)).map(|(_, value)| value)
+}
+
+fn seq(value: Option<&Value>) -> &[Value] {
+	match value {
+		Some(Value::Seq(items)) => items,
+		_ => &[],
+	}
+}
+
+/// Unwraps `Some(...)`. `None` comes out as `None`, anything else as itself.
+fn some(value: &Value) -> Option<&Value> {
+	match value {
+		Value::Option(inner) => inner.as_deref(),
+		other => Some(other),
+	}
+}
+
+fn as_str(value: &Value) -> Option<&str> {
+	match value {
+		Value::String(string) => Some(string),
+		_ => None,
+	}
+}
+
+/// A sprite's name is its path under `SPRITE_DIR`, without the extension.
+fn sprite_name(relative: &Path) -> String {
+	slashed(&relative.strip_prefix(SPRITE_DIR).unwrap_or(relative).with_extension(""))
+}
+
+/// Packs sprites into pages of an atlas, and writes them along with a manifest of where each one ended up. Returns
+/// everything it wrote, relative to `out`.
+fn pack_atlas(src: &Path, inputs: &[PathBuf], out: &Path) -> Result<Vec<String>> {
+	let mut sprites: Vec<(String, RgbaImage)> = Vec::new();
+	for input in inputs {
+		let path = src.join(input);
+		let image = image::open(&path).with_context(|| format!("problem loading {}", path.to_string_lossy()))?.into_rgba8();
+		if image.width() + PADDING * 2 > PAGE_SIZE || image.height() + PADDING * 2 > PAGE_SIZE {
+			bail!("{} is too big to fit on a {}x{} atlas page", path.to_string_lossy(), PAGE_SIZE, PAGE_SIZE);
+		}
+		sprites.push((sprite_name(input), image));
+	}
+
+	//tallest first packs a lot tighter on a skyline
+	sprites.sort_by(|(a_name, a), (b_name, b)| b.height().cmp(&a.height()).then(b.width().cmp(&a.width())).then(a_name.cmp(b_name)));
+
+	let mut pages: Vec<(Skyline, RgbaImage)> = Vec::new();
+	let mut placements: Vec<(String, usize, u32, u32, u32, u32)> = Vec::new();
+	for (sprite_name, image) in &sprites {
+		let (width, height) = (image.width() + PADDING * 2, image.height() + PADDING * 2);
+		let fits = pages.iter_mut().enumerate().find_map(|(index, (skyline, _))| skyline.place(width, height).map(|spot| (index, spot)));
+		let (page, (x, y)) = match fits {
+			Some(found) => found,
+			None => {
+				let mut skyline = Skyline::new(PAGE_SIZE, PAGE_SIZE);
+				let spot = skyline.place(width, height).expect("sprite doesn't fit on an empty page");
+				pages.push((skyline, RgbaImage::new(PAGE_SIZE, PAGE_SIZE)));
+				(pages.len() - 1, spot)
+			},
+		};
+
+		let (x, y) = (x + PADDING, y + PADDING);
+		let canvas = &mut pages[page].1;
+		canvas.copy_from(image, x, y)?;
+		extrude(canvas, x, y, image.width(), image.height());
+		placements.push((sprite_name.clone(), page, x, y, image.width(), image.height()));
+	}
+
+	fs::create_dir_all(out.join(ATLAS_DIR))?;
+	let mut outputs = Vec::new();
+	let mut manifest = String::new();
+	writeln!(manifest, "(")?;
+	writeln!(manifest, "    page_size: {},", PAGE_SIZE)?;
+	writeln!(manifest, "    pages: [")?;
+	for (index, (_, canvas)) in pages.iter().enumerate() {
+		let file = format!("{}/{}_{}.png", ATLAS_DIR, ATLAS_NAME, index);
+		canvas.save(out.join(&file)).with_context(|| format!("problem writing atlas page {}", file))?;
+		writeln!(manifest, "        {:?},", file)?;
+		outputs.push(file);
+	}
+	writeln!(manifest, "    ],")?;
+	writeln!(manifest, "    sprites: {{")?;
+	placements.sort();
+	for (sprite_name, page, x, y, width, height) in placements {
+		writeln!(manifest, "        {:?}: (page: {}, x: {}, y: {}, width: {}, height: {}),", sprite_name, page, x, y, width, height)?;
+	}
+	writeln!(manifest, "    }},")?;
+	writeln!(manifest, ")")?;
+
+	let manifest_file = format!("{}/{}.ron", ATLAS_DIR, ATLAS_NAME);
+	fs::write(out.join(&manifest_file), manifest)?;
+	outputs.push(manifest_file);
+
+	Ok(outputs)
+}
+
+/// Copies the outermost pixels of the sprite at `x, y` outwards by `EXTRUDE` pixels, corners included.
+fn extrude(canvas: &mut RgbaImage, x: u32, y: u32, width: u32, height: u32) {
+	let clamp = |value: i64, start: u32, length: u32| value.max(start as i64).min((start + length - 1) as i64) as u32;
+	let extrude = EXTRUDE as i64;
+	for out_y in y as i64 - extrude..(y + height) as i64 + extrude {
+		for out_x in x as i64 - extrude..(x + width) as i64 + extrude {
+			let (in_x, in_y) = (clamp(out_x, x, width), clamp(out_y, y, height));
+			if (in_x as i64, in_y as i64) != (out_x, out_y) {
+				let pixel = *canvas.get_pixel(in_x, in_y);
+				canvas.put_pixel(out_x as u32, out_y as u32, pixel);
+			}
+		}
+	}
+}
+
+/// Bottom-left skyline bin packing. Keeps track of the top edge of everything placed so far, as a row of flat segments
+/// from left to right, and puts each new rectangle wherever along it ends up lowest.
+struct Skyline {
+	width: u32,
+	height: u32,
+	/// x, y and width of each segment. They cover the whole page width without overlapping.
+	segments: Vec<(u32, u32, u32)>,
+}
+
+impl Skyline {
+	fn new(width: u32, height: u32) -> Self {
+		Skyline { width, height, segments: vec![(0, 0, width)] }
+	}
+
+	/// Finds a spot for a rectangle and marks it as taken. Returns its top left corner, or `None` if it doesn't fit.
+	fn place(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+		let (index, y) = (0..self.segments.len())
+			.filter_map(|index| self.fits(index, width, height).map(|y| (index, y)))
+			.min_by_key(|&(index, y)| (y + height, self.segments[index].0))?;
+		let x = self.segments[index].0;
+
+		//the new segment, then trim whatever it covers up off the ones to its right
+		self.segments.insert(index, (x, y + height, width));
+		let right = x + width;
+		while index + 1 < self.segments.len() && self.segments[index + 1].0 < right {
+			let next = &mut self.segments[index + 1];
+			let covered = right - next.0;
+			if next.2 <= covered {
+				self.segments.remove(index + 1);
+			} else {
+				next.0 += covered;
+				next.2 -= covered;
+				break;
+			}
+		}
+
+		//and join up neighbours at the same height
+		let mut i = 0;
+		while i + 1 < self.segments.len() {
+			if self.segments[i].1 == self.segments[i + 1].1 {
+				self.segments[i].2 += self.segments[i + 1].2;
+				self.segments.remove(i + 1);
+			} else {
+				i += 1;
+			}
+		}
+
+		Some((x, y))
+	}
+
+	/// How high a rectangle would have to sit if its left edge went at the start of this segment.
+	fn fits(&self, index: usize, width: u32, height: u32) -> Option<u32> {
+		let x = self.segments[index].0;
+		if x + width > self.width {
+			return None;
+		}
+		let mut y = 0;
+		let mut covered = 0;
+		for &(_, segment_y, segment_width) in &self.segments[index..] {
+			y = y.max(segment_y);
+			if y + height > self.height {
+				return None;
+			}
+			covered += segment_width;
+			if covered >= width {
+				return Some(y);
+			}
+		}
+		None
+	}
+}
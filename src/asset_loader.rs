@@ -2,6 +2,7 @@ use std::path::PathBuf;
 
 use anyhow::*;
 
+#[derive(Clone)]
 pub struct AssetLoader {
 	base_path: PathBuf,
 }
@@ -31,4 +32,18 @@ impl AssetLoader {
 
 		std::fs::read_to_string(path).with_context(|| format!("failed to load file '{}'", name))
 	}
+
+	pub fn load_bytes(&self, name: &str) -> Result<Vec<u8>> {
+		let mut path = self.base_path.clone();
+		path.push(name);
+
+		std::fs::read(path).with_context(|| format!("failed to load file '{}'", name))
+	}
+
+	/// Decodes a picture (probably a PNG) into RGBA.
+	pub fn load_image(&self, name: &str) -> Result<image::RgbaImage> {
+		let bytes = self.load_bytes(name)?;
+		let image = image::load_from_memory(&bytes).with_context(|| format!("failed to decode image '{}'", name))?;
+		Ok(image.into_rgba8())
+	}
 }
@@ -0,0 +1,323 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::*;
+use ultraviolet::Vec2;
+
+/// A decoded sound, stored as stereo frames at whatever sample rate the file had.
+pub struct Sound {
+	pub frames: Vec<[f32; 2]>,
+	pub sample_rate: u32,
+}
+
+impl Sound {
+	pub fn from_wav_bytes(bytes: &[u8]) -> Result<Self> {
+		let reader = hound::WavReader::new(std::io::Cursor::new(bytes)).context("couldn't read wav header")?;
+		let spec = reader.spec();
+
+		//normalize everything into f32s in -1..1
+		let samples: Vec<f32> = match spec.sample_format {
+			hound::SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<_, _>>()?,
+			hound::SampleFormat::Int => {
+				let scale = 1.0 / (1i64 << (spec.bits_per_sample - 1)) as f32;
+				reader.into_samples::<i32>().map(|s| s.map(|s| s as f32 * scale)).collect::<Result<_, _>>()?
+			},
+		};
+
+		//then smoosh the channels into stereo
+		let frames = match spec.channels {
+			1 => samples.iter().map(|&s| [s, s]).collect(),
+			2 => samples.chunks_exact(2).map(|c| [c[0], c[1]]).collect(),
+			other => bail!("unsupported channel count {}", other),
+		};
+
+		Ok(Sound { frames, sample_rate: spec.sample_rate })
+	}
+}
+
+/// Where sounds are heard from. Sounds get quieter with distance and pan left/right depending on which side they're on.
+#[derive(Copy, Clone, Debug)]
+pub struct Listener {
+	pub position: Vec2,
+	/// Past this distance sounds are silent.
+	pub max_distance: f32,
+	/// How far to the side a sound has to be to come entirely out of one speaker.
+	pub pan_distance: f32,
+}
+
+impl Listener {
+	/// Returns (volume, pan) for a sound played at this position.
+	pub fn spatialize(&self, position: Vec2) -> (f32, f32) {
+		let delta = position - self.position;
+		let volume = (1.0 - delta.mag() / self.max_distance).max(0.0);
+		let pan = (delta.x / self.pan_distance).clamp(-1.0, 1.0);
+		(volume, pan)
+	}
+}
+
+impl Default for Listener {
+	fn default() -> Self {
+		Listener { position: Vec2::zero(), max_distance: 2000.0, pan_distance: 800.0 }
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VoiceId(u64);
+
+struct Voice {
+	id: VoiceId,
+	sound: Arc<Sound>,
+	/// In frames of the source sound. Fractional because the sound might not be at the mixer's sample rate.
+	cursor: f64,
+	volume: f32,
+	pan: f32,
+	looping: bool,
+}
+
+impl Voice {
+	/// Linearly interpolates between the two source frames surrounding the cursor.
+	fn sample(&self) -> [f32; 2] {
+		let frames = &self.sound.frames;
+		let index = self.cursor as usize;
+		let t = (self.cursor - index as f64) as f32;
+
+		let a = frames[index];
+		let b = match frames.get(index + 1) {
+			Some(b) => *b,
+			None if self.looping => frames[0],
+			None => a,
+		};
+
+		[a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t]
+	}
+
+	/// Adds this voice on top of whatever's already in `out`, advancing the cursor as it goes.
+	fn mix_into(&mut self, out: &mut [f32], channels: usize, mixer_rate: u32, master_volume: f32) {
+		let step = self.sound.sample_rate as f64 / mixer_rate as f64;
+		let len = self.sound.frames.len() as f64;
+		let [left_gain, right_gain] = self.gains();
+
+		for frame in out.chunks_exact_mut(channels) {
+			if self.cursor >= len {
+				if self.looping {
+					self.cursor %= len;
+				} else {
+					return;
+				}
+			}
+
+			let [l, r] = self.sample();
+			let l = l * left_gain * master_volume;
+			let r = r * right_gain * master_volume;
+
+			if channels == 1 {
+				frame[0] += (l + r) * 0.5;
+			} else {
+				frame[0] += l;
+				frame[1] += r;
+			}
+
+			self.cursor += step;
+		}
+	}
+
+	/// Equal-power panning, so sounds don't get quieter in the middle.
+	fn gains(&self) -> [f32; 2] {
+		let angle = (self.pan + 1.0) * std::f32::consts::FRAC_PI_4;
+		[angle.cos() * self.volume, angle.sin() * self.volume]
+	}
+}
+
+/// Software mixer. Owns a bunch of playing voices and adds them all together into an output buffer.
+pub struct Mixer {
+	pub sample_rate: u32,
+	pub master_volume: f32,
+	pub max_voices: usize,
+	voices: Vec<Voice>,
+	next_id: u64,
+}
+
+impl Mixer {
+	pub fn new(sample_rate: u32) -> Self {
+		Mixer { sample_rate, master_volume: 1.0, max_voices: 64, voices: Vec::new(), next_id: 0 }
+	}
+
+	/// Start playing a sound. Pan goes from -1 (all left) to 1 (all right).
+	pub fn play(&mut self, sound: &Arc<Sound>, volume: f32, pan: f32, looping: bool) -> VoiceId {
+		//if there's too many sounds going on, cut off the oldest one
+		if self.voices.len() >= self.max_voices {
+			self.voices.remove(0);
+		}
+
+		let id = VoiceId(self.next_id);
+		self.next_id += 1;
+
+		if !sound.frames.is_empty() {
+			self.voices.push(Voice { id, sound: sound.clone(), cursor: 0.0, volume, pan: pan.clamp(-1.0, 1.0), looping });
+		}
+
+		id
+	}
+
+	/// Plays a sound at a position in the world, as heard by the listener.
+	pub fn play_at(&mut self, sound: &Arc<Sound>, volume: f32, position: Vec2, listener: &Listener) -> VoiceId {
+		let (spatial_volume, pan) = listener.spatialize(position);
+		self.play(sound, volume * spatial_volume, pan, false)
+	}
+
+	pub fn stop(&mut self, id: VoiceId) {
+		self.voices.retain(|v| v.id != id);
+	}
+
+	pub fn stop_all(&mut self) {
+		self.voices.clear();
+	}
+
+	pub fn is_playing(&self, id: VoiceId) -> bool {
+		self.voices.iter().any(|v| v.id == id)
+	}
+
+	pub fn voice_count(&self) -> usize {
+		self.voices.len()
+	}
+
+	/// Mixes the next chunk of audio into `out`, which is interleaved with `channels` channels.
+	/// Voices that run out of sound are removed.
+	pub fn render(&mut self, out: &mut [f32], channels: usize) {
+		for sample in out.iter_mut() {
+			*sample = 0.0;
+		}
+
+		if channels == 0 {
+			return;
+		}
+
+		let mixer_rate = self.sample_rate;
+		let master_volume = self.master_volume;
+		for voice in self.voices.iter_mut() {
+			voice.mix_into(out, channels, mixer_rate, master_volume);
+		}
+		self.voices.retain(|v| v.looping || v.cursor < v.sound.frames.len() as f64);
+
+		for sample in out.iter_mut() {
+			*sample = sample.clamp(-1.0, 1.0);
+		}
+	}
+
+	/// Mixes `frame_count` frames of stereo audio into a new buffer, without needing a sound device.
+	pub fn render_to_buffer(&mut self, frame_count: usize) -> Vec<f32> {
+		let mut buffer = vec![0.0; frame_count * 2];
+		self.render(&mut buffer, 2);
+		buffer
+	}
+
+	/// Mixes `frame_count` frames of stereo audio and writes them out to a wav file.
+	pub fn render_to_wav<P: AsRef<Path>>(&mut self, path: P, frame_count: usize) -> Result<()> {
+		let spec = hound::WavSpec { channels: 2, sample_rate: self.sample_rate, bits_per_sample: 32, sample_format: hound::SampleFormat::Float };
+		let mut writer = hound::WavWriter::create(path.as_ref(), spec).with_context(|| format!("couldn't create {:?}", path.as_ref()))?;
+
+		for sample in self.render_to_buffer(frame_count) {
+			writer.write_sample(sample)?;
+		}
+
+		writer.finalize()?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const RATE: u32 = 48000;
+
+	/// `frames` frames of a constant level, as a 16 bit mono wav, then decoded like any other sound.
+	fn constant_sound(level: f32, frames: usize, sample_rate: u32) -> Arc<Sound> {
+		let spec = hound::WavSpec { channels: 1, sample_rate, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+		let mut bytes = std::io::Cursor::new(Vec::new());
+		let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+		for _ in 0..frames {
+			writer.write_sample((level * i16::MAX as f32) as i16).unwrap();
+		}
+		writer.finalize().unwrap();
+		Arc::new(Sound::from_wav_bytes(bytes.get_ref()).unwrap())
+	}
+
+	fn assert_close(actual: f32, expected: f32) {
+		assert!((actual - expected).abs() < 1e-3, "expected {}, got {}", expected, actual);
+	}
+
+	#[test]
+	fn centered_sounds_are_equal_power() {
+		let mut mixer = Mixer::new(RATE);
+		mixer.play(&constant_sound(0.5, 100, RATE), 1.0, 0.0, false);
+		let out = mixer.render_to_buffer(10);
+		for frame in out.chunks_exact(2) {
+			assert_close(frame[0], 0.5 * std::f32::consts::FRAC_1_SQRT_2);
+			assert_close(frame[1], 0.5 * std::f32::consts::FRAC_1_SQRT_2);
+		}
+	}
+
+	#[test]
+	fn panning_and_gain() {
+		let sound = constant_sound(0.5, 100, RATE);
+
+		let mut mixer = Mixer::new(RATE);
+		mixer.play(&sound, 1.0, -1.0, false);
+		let out = mixer.render_to_buffer(10);
+		assert_close(out[0], 0.5);
+		assert_close(out[1], 0.0);
+
+		let mut mixer = Mixer::new(RATE);
+		mixer.master_volume = 0.5;
+		mixer.play(&sound, 0.5, 1.0, false);
+		let out = mixer.render_to_buffer(10);
+		assert_close(out[0], 0.0);
+		assert_close(out[1], 0.125);
+	}
+
+	#[test]
+	fn loud_mixes_get_clipped() {
+		let sound = constant_sound(0.9, 100, RATE);
+		let mut mixer = Mixer::new(RATE);
+		for _ in 0..4 {
+			mixer.play(&sound, 1.0, -1.0, false);
+		}
+		let out = mixer.render_to_buffer(10);
+		for frame in out.chunks_exact(2) {
+			assert_eq!(frame[0], 1.0);
+			assert_close(frame[1], 0.0);
+		}
+	}
+
+	#[test]
+	fn finished_voices_go_quiet_and_get_removed() {
+		let mut mixer = Mixer::new(RATE);
+		let id = mixer.play(&constant_sound(0.5, 4, RATE), 1.0, -1.0, false);
+		let out = mixer.render_to_buffer(8);
+		assert_close(out[3 * 2], 0.5);
+		assert_close(out[4 * 2], 0.0);
+		assert!(!mixer.is_playing(id));
+		assert_eq!(mixer.voice_count(), 0);
+	}
+
+	#[test]
+	fn looping_voices_keep_going() {
+		let mut mixer = Mixer::new(RATE);
+		let id = mixer.play(&constant_sound(0.5, 4, RATE), 1.0, -1.0, true);
+		let out = mixer.render_to_buffer(16);
+		for frame in out.chunks_exact(2) {
+			assert_close(frame[0], 0.5);
+		}
+		assert!(mixer.is_playing(id));
+	}
+
+	#[test]
+	fn slower_sounds_get_stretched() {
+		let mut mixer = Mixer::new(RATE);
+		mixer.play(&constant_sound(0.5, 4, RATE / 2), 1.0, -1.0, false);
+		let out = mixer.render_to_buffer(10);
+		assert_close(out[7 * 2], 0.5);
+		assert_close(out[8 * 2], 0.0);
+	}
+}
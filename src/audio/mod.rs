@@ -0,0 +1,54 @@
+mod mixer;
+mod output;
+mod sound_bank;
+
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use anyhow::*;
+pub use mixer::*;
+pub use output::*;
+pub use sound_bank::*;
+
+use crate::asset_loader::AssetLoader;
+use crate::game::GameEvent;
+
+/// Turns game events into sounds.
+pub struct Audio {
+	pub mixer: Arc<Mutex<Mixer>>,
+	pub listener: Listener,
+	pub sounds: SoundBank,
+	pub effects: SoundEffects,
+	output: Option<AudioOutput>,
+}
+
+impl Audio {
+	pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+	/// Loads every sound mentioned in the sound effects file. Doesn't touch the sound card, call `start_output` for that.
+	pub fn new(asset_loader: &AssetLoader) -> Result<Self> {
+		let effects = SoundEffects::load(asset_loader)?;
+
+		let mut sounds = SoundBank::default();
+		for effect in effects.effects.values() {
+			sounds.load(asset_loader, &effect.sound)?;
+		}
+
+		Ok(Audio { mixer: Arc::new(Mutex::new(Mixer::new(Self::DEFAULT_SAMPLE_RATE))), listener: Listener::default(), sounds, effects, output: None })
+	}
+
+	pub fn start_output(&mut self) -> Result<()> {
+		self.output = Some(AudioOutput::new(self.mixer.clone())?);
+		Ok(())
+	}
+
+	pub fn handle_event(&mut self, event: &GameEvent) {
+		if let GameEvent::Named { name, position } = event {
+			if let Some(effect) = self.effects.effects.get(name) {
+				if let Some(sound) = self.sounds.get(&effect.sound) {
+					self.mixer.lock().unwrap().play_at(sound, effect.volume, *position, &self.listener);
+				}
+			}
+		}
+	}
+}
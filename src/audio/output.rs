@@ -0,0 +1,56 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use anyhow::*;
+use cpal::traits::DeviceTrait;
+use cpal::traits::HostTrait;
+use cpal::traits::StreamTrait;
+
+use crate::audio::Mixer;
+
+/// Keeps a stream to the sound card open, which pulls audio out of the mixer on its own thread.
+/// Audio stops when this is dropped.
+pub struct AudioOutput {
+	_stream: cpal::Stream,
+}
+
+impl AudioOutput {
+	pub fn new(mixer: Arc<Mutex<Mixer>>) -> Result<Self> {
+		let host = cpal::default_host();
+		let device = host.default_output_device().ok_or_else(|| anyhow!("no audio output device"))?;
+		let supported_config = device.default_output_config().context("couldn't get audio output config")?;
+		let sample_format = supported_config.sample_format();
+		let config: cpal::StreamConfig = supported_config.into();
+
+		mixer.lock().unwrap().sample_rate = config.sample_rate.0;
+
+		let stream = match sample_format {
+			cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, mixer),
+			cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, mixer),
+			cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, mixer),
+		}?;
+
+		stream.play().context("couldn't start audio stream")?;
+
+		Ok(AudioOutput { _stream: stream })
+	}
+}
+
+fn build_stream<T: cpal::Sample>(device: &cpal::Device, config: &cpal::StreamConfig, mixer: Arc<Mutex<Mixer>>) -> Result<cpal::Stream> {
+	let channels = config.channels as usize;
+	let mut scratch: Vec<f32> = Vec::new();
+
+	let stream = device.build_output_stream(
+		config,
+		move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+			scratch.resize(data.len(), 0.0);
+			mixer.lock().unwrap().render(&mut scratch, channels);
+			for (out, sample) in data.iter_mut().zip(scratch.iter()) {
+				*out = cpal::Sample::from(sample);
+			}
+		},
+		|err| log::error!("audio stream error: {}", err),
+	)?;
+
+	Ok(stream)
+}
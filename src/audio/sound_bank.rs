@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::*;
+use serde::Deserialize;
+
+use crate::asset_loader::AssetLoader;
+use crate::audio::Sound;
+
+/// All the decoded sounds, by name. "blip" refers to `sounds/blip.wav`.
+#[derive(Default)]
+pub struct SoundBank {
+	sounds: HashMap<String, Arc<Sound>>,
+}
+
+impl SoundBank {
+	pub fn load(&mut self, asset_loader: &AssetLoader, name: &str) -> Result<Arc<Sound>> {
+		if let Some(sound) = self.sounds.get(name) {
+			return Ok(sound.clone());
+		}
+
+		let bytes = asset_loader.load_bytes(&format!("sounds/{}.wav", name))?;
+		let sound = Arc::new(Sound::from_wav_bytes(&bytes).with_context(|| format!("couldn't decode sound '{}'", name))?);
+		self.sounds.insert(name.to_string(), sound.clone());
+
+		Ok(sound)
+	}
+
+	pub fn get(&self, name: &str) -> Option<&Arc<Sound>> {
+		self.sounds.get(name)
+	}
+}
+
+/// Which sound to play when a game event with a certain name happens. Loaded from `sounds/sound_effects.ron`.
+#[derive(Deserialize)]
+pub struct SoundEffects {
+	pub effects: HashMap<String, SoundEffect>,
+}
+
+#[derive(Deserialize)]
+pub struct SoundEffect {
+	pub sound: String,
+	#[serde(default = "one")]
+	pub volume: f32,
+}
+
+fn one() -> f32 {
+	1.0
+}
+
+impl SoundEffects {
+	pub fn load(asset_loader: &AssetLoader) -> Result<Self> {
+		let file = asset_loader.load_string("sounds/sound_effects.ron")?;
+		ron::from_str(&file).with_context(|| anyhow!("couldnt parse sound effects file"))
+	}
+}
@@ -0,0 +1,79 @@
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+use crate::window::WindowSettings;
+use crate::world::GameWorld;
+
+/// Command line arguments.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "ludum-dare-48", about = "Plays the game, or runs it without a window for testing.")]
+pub struct Options {
+	/// Where to load assets from. Defaults to where the build script put them (`target/<profile>/assets`) when run
+	/// through cargo, or the `assets` folder next to the executable otherwise.
+	#[structopt(long, parse(from_os_str))]
+	pub assets: Option<PathBuf>,
+
+	/// Map to load, relative to the asset directory.
+	#[structopt(long, default_value = GameWorld::DEFAULT_MAP)]
+	pub map: String,
+
+	/// Seed for the game's randomness. Random if not set.
+	#[structopt(long)]
+	pub seed: Option<u64>,
+
+	/// Window title.
+	#[structopt(long, default_value = "my game name!")]
+	pub title: String,
+
+	/// Window width, in logical pixels.
+	#[structopt(long, default_value = "1024")]
+	pub width: u32,
+
+	/// Window height, in logical pixels.
+	#[structopt(long, default_value = "576")]
+	pub height: u32,
+
+	/// Borderless fullscreen, on the current monitor.
+	#[structopt(long)]
+	pub fullscreen: bool,
+
+	/// Show frames as soon as they're ready, even if that tears.
+	#[structopt(long)]
+	pub no_vsync: bool,
+
+	/// Log filter, like `info` or `ludum_dare_48=debug,wgpu=warn`. Overrides `RUST_LOG`.
+	#[structopt(long)]
+	pub log_level: Option<String>,
+
+	/// Run the simulation without a window (or sound), then exit.
+	#[structopt(long)]
+	pub headless: bool,
+
+	/// How many ticks to run for, with `--headless`.
+	#[structopt(long, default_value = "600")]
+	pub ticks: u64,
+
+	/// Play a recorded replay without a window, checking it against its checksums, then exit. Fails if it diverges.
+	#[structopt(long, parse(from_os_str))]
+	pub replay: Option<PathBuf>,
+}
+
+impl Options {
+	pub fn asset_path(&self) -> PathBuf {
+		match &self.assets {
+			Some(path) => path.clone(),
+			None if std::env::var_os("CARGO_MANIFEST_DIR").is_some() => PathBuf::from(env!("ASSET_DIR")),
+			None => {
+				let exe = std::env::current_exe().unwrap_or_default();
+				let mut path = exe.parent().map_or_else(|| PathBuf::from("./"), |dir| dir.to_path_buf());
+				path.push("assets");
+				path
+			},
+		}
+	}
+
+	pub fn window_settings(&self) -> WindowSettings {
+		WindowSettings { title: self.title.clone(), logical_width: self.width, logical_height: self.height, fullscreen: self.fullscreen, vsync: !self.no_vsync }
+	}
+}
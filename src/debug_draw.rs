@@ -0,0 +1,197 @@
+//! Shapes drawn over the scene for debugging. They last until the next tick, and compile out without the `debug-draw`
+//! feature.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+use ultraviolet::Vec2;
+use ultraviolet::Vec3;
+
+use crate::world::Polyline;
+
+/// Global, so it sticks around when the game gets replaced by a quickload or a replay.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(feature = "debug-draw")]
+pub fn is_enabled() -> bool {
+	ENABLED.load(Ordering::Relaxed)
+}
+
+#[cfg(not(feature = "debug-draw"))]
+pub fn is_enabled() -> bool {
+	false
+}
+
+/// Turns debug drawing on or off, and says which it ended up as.
+pub fn toggle() -> bool {
+	if cfg!(feature = "debug-draw") {
+		!ENABLED.fetch_xor(true, Ordering::Relaxed)
+	} else {
+		false
+	}
+}
+
+#[derive(Default)]
+pub struct DebugDraw {
+	shapes: Vec<Polyline>,
+}
+
+impl DebugDraw {
+	/// Thin, so it doesn't hide what it's pointing at.
+	pub const THICKNESS: f32 = 1.5;
+	/// Enough that big circles still look round.
+	const CIRCLE_SEGMENTS: usize = 24;
+	pub const RED: Vec3 = Vec3::new(1.0, 0.2, 0.2);
+	pub const GREEN: Vec3 = Vec3::new(0.2, 1.0, 0.3);
+	pub const BLUE: Vec3 = Vec3::new(0.3, 0.5, 1.0);
+	pub const YELLOW: Vec3 = Vec3::new(1.0, 0.9, 0.2);
+	pub const WHITE: Vec3 = Vec3::new(1.0, 1.0, 1.0);
+
+	/// Throws away last tick's shapes.
+	pub fn clear(&mut self) {
+		self.shapes.clear();
+	}
+
+	pub fn shapes(&self) -> &[Polyline] {
+		&self.shapes
+	}
+
+	pub fn line(&mut self, a: Vec2, b: Vec2, color: Vec3) {
+		if is_enabled() {
+			self.polyline(vec![a, b], color);
+		}
+	}
+
+	pub fn polyline(&mut self, points: Vec<Vec2>, color: Vec3) {
+		if is_enabled() && points.len() >= 2 {
+			self.shapes.push(Polyline::new(points, color, Self::THICKNESS));
+		}
+	}
+
+	/// A line with a little head on the `to` end, for directions and normals.
+	pub fn arrow(&mut self, from: Vec2, to: Vec2, color: Vec3) {
+		if !is_enabled() {
+			return;
+		}
+		let along = to - from;
+		let length = along.mag();
+		if length <= f32::EPSILON {
+			return;
+		}
+		let back = along / length * -(length * 0.25).min(8.0);
+		let side = Vec2::new(-back.y, back.x) * 0.5;
+		self.line(from, to, color);
+		self.polyline(vec![to + back + side, to, to + back - side], color);
+	}
+
+	pub fn circle(&mut self, center: Vec2, radius: f32, color: Vec3) {
+		if !is_enabled() {
+			return;
+		}
+		let points = (0..=Self::CIRCLE_SEGMENTS)
+			.map(|i| {
+				let angle = i as f32 / Self::CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+				center + Vec2::new(angle.cos(), angle.sin()) * radius
+			})
+			.collect();
+		self.polyline(points, color);
+	}
+
+	pub fn aabb(&mut self, min: Vec2, max: Vec2, color: Vec3) {
+		if is_enabled() {
+			self.polyline(vec![min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y), min], color);
+		}
+	}
+
+	/// Writes `text` with its top left corner at `position`. `size` is how tall capital letters are, in world units.
+	/// Only has letters, digits and a bit of punctuation. Lowercase comes out as uppercase, and anything else as a box.
+	pub fn text(&mut self, position: Vec2, text: &str, size: f32, color: Vec3) {
+		if !is_enabled() {
+			return;
+		}
+		let unit = size / font::HEIGHT;
+		let mut cursor = position;
+		for c in text.chars() {
+			if c == '\n' {
+				cursor = Vec2::new(position.x, cursor.y + font::LINE_HEIGHT * unit);
+				continue;
+			}
+			for stroke in font::glyph(c).split(' ').filter(|stroke| !stroke.is_empty()) {
+				let digits: Vec<f32> = stroke.bytes().map(|digit| (digit - b'0') as f32).collect();
+				self.polyline(digits.chunks_exact(2).map(|xy| cursor + Vec2::new(xy[0], xy[1]) * unit).collect(), color);
+			}
+			cursor.x += font::ADVANCE * unit;
+		}
+	}
+}
+
+/// A tiny stroke font on a grid 4 wide and 6 tall, y going down. Each glyph is strokes separated by spaces, and each
+/// stroke is the points it goes through as pairs of digits, x then y.
+mod font {
+	pub const HEIGHT: f32 = 6.0;
+	pub const ADVANCE: f32 = 6.0;
+	pub const LINE_HEIGHT: f32 = 9.0;
+
+	pub fn glyph(c: char) -> &'static str {
+		match c.to_ascii_uppercase() {
+			' ' => "",
+			'0' => "0040460600 0640",
+			'1' => "112026 1636",
+			'2' => "004043030646",
+			'3' => "00404606 0343",
+			'4' => "000343 4046",
+			'5' => "40000232434606",
+			'6' => "400006464303",
+			'7' => "004026",
+			'8' => "0040460600 0343",
+			'9' => "430300404606",
+			'A' => "0602204246 0343",
+			'B' => "00304142334445360600 0333",
+			'C' => "40000646",
+			'D' => "00304244360600",
+			'E' => "40000646 0333",
+			'F' => "400006 0333",
+			'G' => "400006464323",
+			'H' => "0006 4046 0343",
+			'I' => "0040 2026 0646",
+			'J' => "40460604",
+			'K' => "0006 400346",
+			'L' => "000646",
+			'M' => "0600234046",
+			'N' => "06004640",
+			'O' => "0040460600",
+			'P' => "0600404303",
+			'Q' => "0040460600 2346",
+			'R' => "060040430346",
+			'S' => "400003434606",
+			'T' => "0040 2026",
+			'U' => "00064640",
+			'V' => "002640",
+			'W' => "0016233640",
+			'X' => "0046 4006",
+			'Y' => "0023 4023 2326",
+			'Z' => "00400646",
+			'.' => "2526",
+			',' => "2516",
+			':' => "2122 2425",
+			'-' => "0343",
+			'+' => "0343 2125",
+			'=' => "0242 0444",
+			'/' => "0640",
+			'(' => "301336",
+			')' => "102316",
+			'[' => "30101636",
+			']' => "10303616",
+			'{' => "30212213242536",
+			'}' => "10212233242516",
+			'<' => "400346",
+			'>' => "004306",
+			'!' => "2024 2526",
+			'?' => "01103041422324 2526",
+			'_' => "0646",
+			'\'' => "2021",
+			'*' => "1135 3115",
+			_ => "0040460600",
+		}
+	}
+}
@@ -1,22 +1,202 @@
 use anyhow::*;
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
+use serde::Deserialize;
+use serde::Serialize;
+use ultraviolet::Vec2;
 
 use crate::asset_loader::AssetLoader;
+use crate::debug_draw::DebugDraw;
+use crate::input::Actions;
+use crate::world::ai;
+use crate::world::destruction::Cut;
+use crate::world::gravity;
+use crate::world::rope;
+use crate::world::systems;
+use crate::world::trigger;
+use crate::world::trigger::TriggerAction;
+use crate::world::trigger::TriggerPhase;
+use crate::world::EntityId;
 use crate::world::GameWorld;
+use crate::world::Polyline;
 
+/// Everything in here gets saved, except for the bits marked `serde(skip)`.
+#[derive(Serialize, Deserialize)]
 pub struct Game {
+	/// Which map file the world was loaded from.
+	pub map: String,
+	/// What the rng was seeded with at the start.
+	pub seed: u64,
 	pub world: GameWorld,
+	/// How many times `update` has been called.
+	pub tick: u64,
+	pub timers: Vec<Timer>,
+	/// Where `TriggerAction::Respawn` sends things.
+	pub checkpoint: Option<Checkpoint>,
+	/// All randomness in the game should come from here, so saving and loading doesn't change what happens next.
+	pub rng: Pcg32,
+	/// Things that happened since the last time someone drained this, for other systems (like audio) to react to.
+	#[serde(skip)]
+	pub events: Vec<GameEvent>,
+	/// Shapes to draw over the scene, for debugging. Cleared at the start of every tick.
+	#[serde(skip)]
+	pub debug: DebugDraw,
 }
 
 impl Game {
-	pub fn load(asset_loader: &AssetLoader) -> Result<Self> {
-		Ok(Game { world: GameWorld::load(asset_loader)? })
+	pub const TICKS_PER_SECOND: u32 = 60;
+
+	pub fn new(asset_loader: &AssetLoader, map: &str, seed: u64) -> Result<Self> {
+		Ok(Game {
+			map: map.to_string(),
+			seed,
+			world: GameWorld::load(asset_loader, map, seed)?,
+			tick: 0,
+			timers: Vec::new(),
+			checkpoint: None,
+			rng: Pcg32::seed_from_u64(seed),
+			events: Vec::new(),
+			debug: DebugDraw::default(),
+		})
+	}
+
+	/// Advances the game by one tick. Everything that happens in here has to be deterministic, given the same `Actions`.
+	pub fn update(&mut self, actions: &Actions) {
+		self.tick += 1;
+		let dt = 1.0 / Self::TICKS_PER_SECOND as f32;
+		self.debug.clear();
+
+		self.world.stream();
+
+		ai::think(&mut self.world, &mut self.rng, dt, &mut self.events, &mut self.debug);
+
+		let entities = &mut self.world.entities;
+		systems::player_control(entities, actions, dt);
+		gravity::kick(&self.world.gravity, entities, dt / 2.0);
+		systems::integrate(entities, dt);
+		gravity::kick(&self.world.gravity, entities, dt / 2.0);
+		systems::wrap_rotations(entities);
+		systems::collide_with_world(entities, &self.world.lines, &mut self.events, &mut self.debug);
+		rope::simulate_ropes(&mut self.world.ropes, entities, &self.world.lines, &self.world.gravity, dt);
+		systems::touch_players(entities, &mut self.events);
+		if let Some(focus) = self.world.focus() {
+			self.world.explored.reveal(focus);
+		}
+
+		if actions.click
+			&& self.world.lines.lines_near(actions.cursor, 0.0).into_iter().filter_map(|id| self.world.lines.get(id)).any(|line| line.contains(actions.cursor))
+		{
+			self.events.push(GameEvent::Named { name: "poke".into(), position: actions.cursor });
+		}
+
+		for timer in self.timers.iter_mut() {
+			timer.remaining = timer.remaining.saturating_sub(1);
+			if timer.remaining == 0 {
+				self.events.push(GameEvent::TimerFinished { name: timer.name.clone() });
+			}
+		}
+		self.timers.retain(|t| t.remaining > 0);
+
+		for fired in trigger::update_triggers(&mut self.world.triggers, &self.world.entities) {
+			let trigger = &self.world.triggers[fired.trigger];
+			self.events.push(GameEvent::Trigger { name: trigger.name.clone(), phase: fired.phase, entity: fired.entity });
+
+			for action in trigger.actions(fired.phase).to_vec() {
+				self.run_trigger_action(&action, fired.entity);
+			}
+		}
+	}
+
+	fn run_trigger_action(&mut self, action: &TriggerAction, entity: EntityId) {
+		let entities = &mut self.world.entities;
+		let position = entities.transforms.get(entity).map_or(Vec2::zero(), |t| t.position);
+
+		match action {
+			TriggerAction::Checkpoint => self.checkpoint = Some(Checkpoint { position }),
+			TriggerAction::Respawn => {
+				if let (Some(checkpoint), Some(transform)) = (&self.checkpoint, entities.transforms.get_mut(entity)) {
+					transform.position = checkpoint.position;
+					if let Some(velocity) = entities.velocities.get_mut(entity) {
+						*velocity = Default::default();
+					}
+				}
+			},
+			TriggerAction::Message(text) => self.events.push(GameEvent::Message(text.clone())),
+			TriggerAction::OpenDoor(tag) => {
+				let doors: Vec<_> = entities.with_tag(tag).collect();
+				for door in doors {
+					if let Some(transform) = entities.transforms.get(door) {
+						self.events.push(GameEvent::Named { name: "door".into(), position: transform.position });
+					}
+					entities.despawn(door);
+				}
+			},
+			TriggerAction::Event(name) => self.events.push(GameEvent::Named { name: name.clone(), position }),
+			TriggerAction::StartTimer(name, ticks) => self.start_timer(name, *ticks),
+			TriggerAction::Crater(radius) => {
+				self.world.cut(&Cut::Circle { center: position, radius: *radius });
+				self.events.push(GameEvent::Named { name: "crater".into(), position });
+			},
+		}
+	}
+
+	/// Where the player will drift over the next `ticks` ticks if they let go of everything, or `None` if there's no
+	/// player.
+	pub fn predict_player(&self, ticks: u32) -> Option<Polyline> {
+		let entities = &self.world.entities;
+		let player = entities.with_tag("player").find(|id| entities.velocities.get(*id).is_some())?;
+		let position = entities.transforms.get(player)?.position;
+		let velocity = entities.velocities.get(player)?.linear;
+
+		let dt = 1.0 / Self::TICKS_PER_SECOND as f32;
+		Some(gravity::predict_trajectory(&self.world.gravity, &self.world.lines, position, velocity, systems::SHIP_DRAG, ticks, dt))
+	}
+
+	/// Starts a timer that fires a `TimerFinished` event after `ticks` updates. Restarts it if it's already running.
+	pub fn start_timer(&mut self, name: &str, ticks: u64) {
+		self.timers.retain(|t| t.name != name);
+		self.timers.push(Timer { name: name.to_string(), remaining: ticks.max(1) });
 	}
 
-	pub fn handle_input(&mut self, _event: &winit::event::WindowEvent) -> bool {
-		false
+	pub fn timer_running(&self, name: &str) -> bool {
+		self.timers.iter().any(|t| t.name == name)
 	}
 
-	pub fn update(&mut self) {
-		//Do nothing
+	/// Hash of all the saved state. If two games have the same checksum on the same tick, they're (almost certainly) in sync.
+	pub fn checksum(&self) -> u64 {
+		let serialized = ron::to_string(self).expect("couldn't serialize game for checksum");
+		crate::util::fnv1a(serialized.as_bytes())
 	}
 }
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Timer {
+	pub name: String,
+	pub remaining: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+	#[serde(with = "crate::util::NotVec2")]
+	pub position: Vec2,
+}
+
+#[derive(Clone, Debug)]
+pub enum GameEvent {
+	/// Something called `name` happened at `position`, in world coordinates.
+	Named {
+		name: String,
+		position: Vec2,
+	},
+	TimerFinished {
+		name: String,
+	},
+	/// A map trigger noticed an entity.
+	Trigger {
+		name: String,
+		phase: TriggerPhase,
+		entity: EntityId,
+	},
+	/// Some text the player should see.
+	Message(String),
+}
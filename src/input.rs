@@ -0,0 +1,74 @@
+use serde::Deserialize;
+use serde::Serialize;
+use ultraviolet::Vec2;
+use winit::event::ElementState;
+use winit::event::KeyboardInput;
+use winit::event::MouseButton;
+use winit::event::VirtualKeyCode;
+use winit::event::WindowEvent;
+
+/// Everything the player asked for during one tick. `Game::update` only ever looks at this, never at raw window events,
+/// so a recording of these is enough to replay a session exactly.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Actions {
+	pub up: bool,
+	pub down: bool,
+	pub left: bool,
+	pub right: bool,
+	pub fire: bool,
+	/// Mouse position, in world coordinates. `InputState` only knows about the screen, so whoever takes the actions
+	/// has to move this into the world.
+	#[serde(with = "crate::util::NotVec2")]
+	pub cursor: Vec2,
+	/// Whether the left mouse button was pressed (not held) this tick.
+	pub click: bool,
+}
+
+/// Collects window events between ticks and turns them into `Actions`.
+#[derive(Default)]
+pub struct InputState {
+	held: Actions,
+	clicked: bool,
+}
+
+impl InputState {
+	/// Returns true if the event was consumed.
+	pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+		match event {
+			WindowEvent::KeyboardInput { input: KeyboardInput { state, virtual_keycode: Some(key), .. }, .. } => {
+				let pressed = *state == ElementState::Pressed;
+				let action = match key {
+					VirtualKeyCode::W | VirtualKeyCode::Up => &mut self.held.up,
+					VirtualKeyCode::S | VirtualKeyCode::Down => &mut self.held.down,
+					VirtualKeyCode::A | VirtualKeyCode::Left => &mut self.held.left,
+					VirtualKeyCode::D | VirtualKeyCode::Right => &mut self.held.right,
+					VirtualKeyCode::Space => &mut self.held.fire,
+					_ => return false,
+				};
+				*action = pressed;
+				true
+			},
+			WindowEvent::CursorMoved { position, .. } => {
+				self.held.cursor = Vec2::new(position.x as f32, position.y as f32);
+				false
+			},
+			WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, .. } => {
+				self.clicked = true;
+				true
+			},
+			WindowEvent::Focused(false) => {
+				//don't keep flying into a wall after alt-tabbing
+				self.held = Actions { cursor: self.held.cursor, ..Default::default() };
+				false
+			},
+			_ => false,
+		}
+	}
+
+	/// Call once per tick. Clicks only count for the first tick after they happen.
+	pub fn take_actions(&mut self) -> Actions {
+		let actions = Actions { click: self.clicked, ..self.held };
+		self.clicked = false;
+		actions
+	}
+}
@@ -1,39 +1,86 @@
 #![feature(array_windows)]
 #![allow(dead_code)] //for now
 
-use std::path::PathBuf;
-
 use anyhow::*;
 use asset_loader::AssetLoader;
+use audio::Audio;
+use cli::Options;
 use game::Game;
 use render::GameRenderer;
+use replay::Replay;
+use structopt::StructOpt;
 use window::GameWindow;
 
 mod asset_loader;
+mod audio;
+mod cli;
+mod debug_draw;
 mod game;
+mod input;
 mod render;
+mod replay;
+mod save;
 mod util;
 mod window;
 mod world;
 
 fn main() -> Result<()> {
-	env_logger::init();
+	let options = Options::from_args();
 
-	let mut asset_path: PathBuf = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| "./".to_string()).into();
-	asset_path.push("assets");
+	let mut logger = env_logger::Builder::from_default_env();
+	if let Some(filter) = &options.log_level {
+		logger.parse_filters(filter);
+	}
+	logger.init();
 
+	let asset_path = options.asset_path();
 	println!("asset base path: {:?}", asset_path);
 
 	let asset_loader = AssetLoader::new(asset_path);
-	let game = Game::load(&asset_loader)?;
 
-	let window = GameWindow::new("my game name!", 1024, 576)?;
+	if let Some(path) = &options.replay {
+		let start = std::time::Instant::now();
+		let game = replay::run_headless(&asset_loader, Replay::load(path)?)?;
+		println!("replayed {} ticks of '{}' (seed {}) in {:?}, checksum {:016x}", game.tick, game.map, game.seed, start.elapsed(), game.checksum());
+		return Ok(());
+	}
+
+	let mut game = Game::new(&asset_loader, &options.map, options.seed.unwrap_or_else(rand::random))?;
+
+	if options.headless {
+		run_headless(&mut game, options.ticks);
+		return Ok(());
+	}
+
+	let mut audio = Audio::new(&asset_loader).context("unable to load audio")?;
+	if let Err(e) = audio.start_output() {
+		//not the end of the world, just play without sound
+		log::warn!("couldn't start audio output: {:?}", e);
+	}
+
+	let window = GameWindow::new(&options.window_settings())?;
 	let mut renderer = futures::executor::block_on(GameRenderer::new(&window, &asset_loader)).context("unable to create game renderer")?;
 
 	//set up
-	renderer.setup(&game);
+	renderer.setup(&mut game);
 
 	//go
-	window.run_loop(game, renderer); //Never returns
+	window.run_loop(asset_loader, game, renderer, audio); //Never returns
 	unreachable!()
 }
+
+/// Runs the game with nobody pressing anything, as fast as it'll go. Handy for checking that maps load and that the
+/// simulation doesn't blow up, without a GPU.
+fn run_headless(game: &mut Game, ticks: u64) {
+	let actions = Default::default();
+	let start = std::time::Instant::now();
+
+	for _ in 0..ticks {
+		game.update(&actions);
+		for event in game.events.drain(..) {
+			log::debug!("tick {}: {:?}", game.tick, event);
+		}
+	}
+
+	println!("ran {} ticks of '{}' (seed {}) in {:?}, checksum {:016x}", ticks, game.map, game.seed, start.elapsed(), game.checksum());
+}
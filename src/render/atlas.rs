@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+use anyhow::*;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::asset_loader::AssetLoader;
+
+/// Where the build script packed each sprite from `asset_src/sprites`. Loaded from `atlases/sprites.ron`, which gets
+/// written along with the pages themselves.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Atlas {
+	/// Width and height of every page, in pixels.
+	pub page_size: u32,
+	/// Paths to the page images, relative to the asset directory.
+	pub pages: Vec<String>,
+	/// Keyed by the sprite's path under `asset_src/sprites`, without the extension.
+	pub sprites: HashMap<String, AtlasSprite>,
+}
+
+/// A sprite's spot on a page, in pixels, not counting the padding around it.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct AtlasSprite {
+	pub page: usize,
+	pub x: u32,
+	pub y: u32,
+	pub width: u32,
+	pub height: u32,
+}
+
+impl Atlas {
+	pub const FILE: &'static str = "atlases/sprites.ron";
+
+	pub fn load(asset_loader: &AssetLoader) -> Result<Self> {
+		let file = asset_loader.load_string(Self::FILE)?;
+		ron::from_str(&file).with_context(|| anyhow!("couldnt parse sprite atlas"))
+	}
+
+	/// The page a sprite is on, and where it is on the page in texture coordinates: left, top, right, bottom.
+	pub fn resolve(&self, name: &str) -> Option<(&str, [f32; 4])> {
+		let sprite = self.sprites.get(name)?;
+		let page = self.pages.get(sprite.page)?;
+		let size = self.page_size as f32;
+		let (left, top) = (sprite.x as f32 / size, sprite.y as f32 / size);
+		Some((page, [left, top, left + sprite.width as f32 / size, top + sprite.height as f32 / size]))
+	}
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use anyhow::*;
+use noise::NoiseFn;
+use noise::OpenSimplex;
+use noise::Seedable;
+use serde::Deserialize;
+use serde::Serialize;
+use ultraviolet::Mat4;
+use ultraviolet::Vec2;
+use ultraviolet::Vec3;
+use winit::dpi::PhysicalSize;
+
+use crate::asset_loader::AssetLoader;
+use crate::game::GameEvent;
+
+/// How the camera moves. Loaded from `render/camera.ron`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CameraSettings {
+	/// Radians per second. Critically damped, so it never overshoots.
+	pub stiffness: f32,
+	/// Seconds.
+	pub look_ahead: f32,
+	pub max_look_ahead: f32,
+	/// Half size of the box the focus can move around in without the camera following.
+	#[serde(with = "crate::util::NotVec2")]
+	pub dead_zone: Vec2,
+	pub bounds_margin: Option<f32>,
+
+	/// Pixels, at full trauma.
+	pub shake_offset: f32,
+	/// Radians, at full trauma.
+	pub shake_angle: f32,
+	pub shake_frequency: f32,
+	/// Per second.
+	pub trauma_decay: f32,
+	#[serde(default)]
+	pub trauma_events: HashMap<String, f32>,
+	/// Events further than this from the camera don't add any trauma.
+	pub trauma_distance: f32,
+}
+
+impl CameraSettings {
+	pub const FILE: &'static str = "render/camera.ron";
+
+	pub fn load(asset_loader: &AssetLoader) -> Result<Self> {
+		let file = asset_loader.load_string(Self::FILE)?;
+		ron::from_str(&file).with_context(|| anyhow!("couldnt parse camera settings"))
+	}
+}
+
+/// Follows the focus around on a spring, and shakes.
+pub struct Camera {
+	pub settings: CameraSettings,
+	/// Middle of the screen in world coordinates, before shaking.
+	pub position: Vec2,
+	velocity: Vec2,
+	/// Only moves when the focus leaves the dead zone around it.
+	anchor: Vec2,
+	pub world_bounds: Option<(Vec2, Vec2)>,
+	/// 0 to 1. Shake goes with trauma squared.
+	pub trauma: f32,
+	time: f32,
+	noise: OpenSimplex,
+}
+
+impl Camera {
+	pub fn new(size: PhysicalSize<u32>, settings: CameraSettings) -> Self {
+		let position = Self::half_size(size);
+		Camera {
+			settings,
+			position,
+			velocity: Vec2::zero(),
+			anchor: position,
+			world_bounds: None,
+			trauma: 0.0,
+			time: 0.0,
+			noise: OpenSimplex::new().set_seed(0),
+		}
+	}
+
+	fn half_size(size: PhysicalSize<u32>) -> Vec2 {
+		Vec2::new(size.width as f32, size.height as f32) / 2.0
+	}
+
+	pub fn snap_to(&mut self, focus: Vec2, size: PhysicalSize<u32>) {
+		self.anchor = focus;
+		self.position = self.clamped(focus, size);
+		self.velocity = Vec2::zero();
+		self.trauma = 0.0;
+	}
+
+	/// `focus` is the position and velocity of whatever to follow.
+	pub fn update(&mut self, focus: Option<(Vec2, Vec2)>, size: PhysicalSize<u32>, dt: f32) {
+		let s = &self.settings;
+		self.time += dt;
+		self.trauma = (self.trauma - s.trauma_decay * dt).max(0.0);
+
+		let (focus, focus_velocity) = match focus {
+			Some(focus) => focus,
+			None => return,
+		};
+
+		//drag the anchor along when the focus pushes on the edge of the dead zone
+		let offset = focus - self.anchor;
+		let excess = offset - offset.clamped(-s.dead_zone, s.dead_zone);
+		self.anchor += excess;
+
+		let mut look_ahead = focus_velocity * s.look_ahead;
+		if look_ahead.mag() > s.max_look_ahead {
+			look_ahead = look_ahead.normalized() * s.max_look_ahead;
+		}
+		let target = self.clamped(self.anchor + look_ahead, size);
+
+		//critically damped spring, solved exactly, so it's stable at any frame rate
+		let omega = s.stiffness;
+		let decay = (-omega * dt).exp();
+		let displacement = self.position - target;
+		let temp = (self.velocity + displacement * omega) * dt;
+		self.velocity = (self.velocity - temp * omega) * decay;
+		self.position = target + (displacement + temp) * decay;
+	}
+
+	/// If the world is smaller than the view, it stays in the middle.
+	fn clamped(&self, position: Vec2, size: PhysicalSize<u32>) -> Vec2 {
+		let (min, max, margin) = match (self.world_bounds, self.settings.bounds_margin) {
+			(Some((min, max)), Some(margin)) => (min, max, margin),
+			_ => return position,
+		};
+		let (min, max) = (min - Vec2::broadcast(margin) + Self::half_size(size), max + Vec2::broadcast(margin) - Self::half_size(size));
+
+		let clamp_axis = |value: f32, min: f32, max: f32| if min > max { (min + max) / 2.0 } else { value.clamp(min, max) };
+		Vec2::new(clamp_axis(position.x, min.x, max.x), clamp_axis(position.y, min.y, max.y))
+	}
+
+	pub fn add_trauma(&mut self, amount: f32) {
+		self.trauma = (self.trauma + amount).min(1.0);
+	}
+
+	pub fn handle_event(&mut self, event: &GameEvent) {
+		if let GameEvent::Named { name, position } = event {
+			if let Some(&amount) = self.settings.trauma_events.get(name) {
+				let closeness = (1.0 - (*position - self.position).mag() / self.settings.trauma_distance).max(0.0);
+				self.add_trauma(amount * closeness);
+			}
+		}
+	}
+
+	fn shake(&self) -> (Vec2, f32) {
+		let shake = self.trauma * self.trauma;
+		if shake <= 0.0 {
+			return (Vec2::zero(), 0.0);
+		}
+
+		let t = (self.time * self.settings.shake_frequency) as f64;
+		let sample = |channel: f64| self.noise.get([t, channel]) as f32;
+		let offset = Vec2::new(sample(0.0), sample(10.0)) * (self.settings.shake_offset * shake);
+		(offset, sample(20.0) * self.settings.shake_angle * shake)
+	}
+
+	pub fn view_matrix(&self, size: PhysicalSize<u32>) -> Mat4 {
+		let (offset, angle) = self.shake();
+		let center = Self::half_size(size) + offset;
+		Mat4::from_translation(Vec3::new(center.x, center.y, 0.0))
+			* Mat4::from_rotation_z(angle)
+			* Mat4::from_translation(Vec3::new(-self.position.x, -self.position.y, 0.0))
+	}
+
+	/// Shake is left out, so the cursor doesn't jitter around in the world.
+	pub fn screen_to_world(&self, screen: Vec2, size: PhysicalSize<u32>) -> Vec2 {
+		screen - Self::half_size(size) + self.position
+	}
+
+	pub fn world_to_screen(&self, world: Vec2, size: PhysicalSize<u32>) -> Vec2 {
+		world - self.position + Self::half_size(size)
+	}
+}
@@ -0,0 +1,207 @@
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::*;
+use wgpu::*;
+use winit::dpi::PhysicalSize;
+
+use crate::render::GameRendererBits;
+
+/// Screenshots and frame sequences as PNGs. Swapchain textures can't be read back, so while capturing the last post
+/// process pass also draws into a texture of ours.
+pub struct Capture {
+	/// Sequences get a folder in here each.
+	dir: PathBuf,
+	screenshot: bool,
+	sequence: Option<Sequence>,
+	target: Option<CaptureTarget>,
+}
+
+struct Sequence {
+	dir: PathBuf,
+	index: u32,
+	remaining: u32,
+}
+
+struct CaptureTarget {
+	size: PhysicalSize<u32>,
+	texture: Texture,
+	view: TextureView,
+	buffer: Buffer,
+	/// Row stride in bytes, padded to `COPY_BYTES_PER_ROW_ALIGNMENT`.
+	padded_row: u32,
+}
+
+impl Capture {
+	pub const SEQUENCE_LENGTH: u32 = 180;
+
+	pub fn new(dir: impl Into<PathBuf>) -> Self {
+		Capture { dir: dir.into(), screenshot: false, sequence: None, target: None }
+	}
+
+	pub fn screenshot(&mut self) {
+		self.screenshot = true;
+	}
+
+	/// Stops an earlier sequence if there is one.
+	pub fn start_sequence(&mut self, frames: u32) {
+		let dir = self.dir.join(timestamp());
+		log::info!("capturing {} frames to {:?}", frames, dir);
+		self.sequence = Some(Sequence { dir, index: 0, remaining: frames });
+	}
+
+	pub fn stop_sequence(&mut self) {
+		if let Some(sequence) = self.sequence.take() {
+			log::info!("stopped capturing to {:?} after {} frames", sequence.dir, sequence.index);
+		}
+	}
+
+	pub fn is_capturing_sequence(&self) -> bool {
+		self.sequence.is_some()
+	}
+
+	fn wants_frame(&self) -> bool {
+		self.screenshot || self.sequence.is_some()
+	}
+
+	/// A view the final image should also be drawn into, if this frame is being captured. Call `finish_frame` after
+	/// submitting.
+	pub fn begin_frame(&mut self, bits: &GameRendererBits) -> Option<&TextureView> {
+		if !self.wants_frame() {
+			return None;
+		}
+		if !matches!(&self.target, Some(target) if target.size == bits.size) {
+			self.target = Some(CaptureTarget::new(bits));
+		}
+		self.target.as_ref().map(|target| &target.view)
+	}
+
+	/// Has to go in the same encoder as the passes that drew it.
+	pub fn copy_frame(&self, encoder: &mut CommandEncoder) {
+		if let (true, Some(target)) = (self.wants_frame(), &self.target) {
+			target.copy(encoder);
+		}
+	}
+
+	/// Blocks on the gpu, but the PNG gets written on another thread.
+	pub fn finish_frame(&mut self, bits: &GameRendererBits) {
+		let target = match (self.wants_frame(), &self.target) {
+			(true, Some(target)) => target,
+			_ => return,
+		};
+
+		let image = match target.read(bits) {
+			Ok(image) => image,
+			Err(e) => {
+				log::error!("couldn't read back frame: {:?}", e);
+				self.screenshot = false;
+				self.sequence = None;
+				return;
+			},
+		};
+
+		if self.screenshot {
+			self.screenshot = false;
+			let path = self.dir.join(format!("{}.png", timestamp()));
+			log::info!("saving screenshot to {:?}", path);
+			save_in_background(image.clone(), path);
+		}
+
+		if let Some(sequence) = &mut self.sequence {
+			save_in_background(image, sequence.dir.join(format!("frame_{:05}.png", sequence.index)));
+			sequence.index += 1;
+			sequence.remaining -= 1;
+			if sequence.remaining == 0 {
+				log::info!("captured {} frames to {:?}", sequence.index, sequence.dir);
+				self.sequence = None;
+			}
+		}
+	}
+}
+
+impl CaptureTarget {
+	const BYTES_PER_PIXEL: u32 = 4;
+
+	fn new(bits: &GameRendererBits) -> Self {
+		let size = bits.size;
+		let texture = bits.device.create_texture(&TextureDescriptor {
+			label: Some("Capture target"),
+			size: Extent3d { width: size.width, height: size.height, depth: 1 },
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: TextureDimension::D2,
+			format: bits.sc_desc.format,
+			usage: TextureUsage::RENDER_ATTACHMENT | TextureUsage::COPY_SRC,
+		});
+		let view = texture.create_view(&TextureViewDescriptor::default());
+
+		let unpadded_row = size.width * Self::BYTES_PER_PIXEL;
+		let padded_row = unpadded_row + (COPY_BYTES_PER_ROW_ALIGNMENT - unpadded_row % COPY_BYTES_PER_ROW_ALIGNMENT) % COPY_BYTES_PER_ROW_ALIGNMENT;
+		let buffer = bits.device.create_buffer(&BufferDescriptor {
+			label: Some("Capture readback buffer"),
+			size: padded_row as BufferAddress * size.height as BufferAddress,
+			usage: BufferUsage::MAP_READ | BufferUsage::COPY_DST,
+			mapped_at_creation: false,
+		});
+
+		CaptureTarget { size, texture, view, buffer, padded_row }
+	}
+
+	fn copy(&self, encoder: &mut CommandEncoder) {
+		encoder.copy_texture_to_buffer(
+			TextureCopyView { texture: &self.texture, mip_level: 0, origin: Origin3d::ZERO },
+			BufferCopyView { buffer: &self.buffer, layout: TextureDataLayout { offset: 0, bytes_per_row: self.padded_row, rows_per_image: self.size.height } },
+			Extent3d { width: self.size.width, height: self.size.height, depth: 1 },
+		);
+	}
+
+	/// Tightly packed RGBA.
+	fn read(&self, bits: &GameRendererBits) -> Result<image::RgbaImage> {
+		let slice = self.buffer.slice(..);
+		let mapping = slice.map_async(MapMode::Read);
+		bits.device.poll(Maintain::Wait);
+		futures::executor::block_on(mapping).context("couldn't map capture buffer")?;
+
+		let swap_red_blue = matches!(bits.sc_desc.format, TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb);
+		let row = (self.size.width * Self::BYTES_PER_PIXEL) as usize;
+		let mut pixels = Vec::with_capacity(row * self.size.height as usize);
+		{
+			let data = slice.get_mapped_range();
+			for padded in data.chunks(self.padded_row as usize) {
+				pixels.extend_from_slice(&padded[..row]);
+			}
+		}
+		self.buffer.unmap();
+
+		if swap_red_blue {
+			for pixel in pixels.chunks_exact_mut(4) {
+				pixel.swap(0, 2);
+			}
+		}
+
+		image::RgbaImage::from_raw(self.size.width, self.size.height, pixels).ok_or_else(|| anyhow!("capture buffer was the wrong size"))
+	}
+}
+
+fn save_in_background(image: image::RgbaImage, path: PathBuf) {
+	std::thread::spawn(move || match save(&image, &path) {
+		Ok(_) => log::debug!("saved {:?}", path),
+		Err(e) => log::error!("couldn't save {:?}: {:?}", path, e),
+	});
+}
+
+fn save(image: &image::RgbaImage, path: &Path) -> Result<()> {
+	if let Some(parent) = path.parent() {
+		std::fs::create_dir_all(parent).with_context(|| anyhow!("couldn't create {:?}", parent))?;
+	}
+	image.save(path)?;
+	Ok(())
+}
+
+/// Milliseconds, so captures sort in order.
+fn timestamp() -> String {
+	let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+	format!("capture_{}", since_epoch.as_millis())
+}
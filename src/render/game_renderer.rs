@@ -1,53 +1,215 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::time::Instant;
+
 use anyhow::*;
 use util::DeviceExt;
 use wgpu::*;
 use winit::dpi::PhysicalSize;
 
 use crate::asset_loader::AssetLoader;
+use crate::debug_draw;
 use crate::game::Game;
+use crate::render::Camera;
+use crate::render::CameraSettings;
+use crate::render::Capture;
+use crate::render::LineMesh;
+use crate::render::Minimap;
+use crate::render::MinimapSettings;
 use crate::render::PolylineBuffer;
 use crate::render::PolylineRenderer;
+use crate::render::PostProcess;
+use crate::render::PostProcessSettings;
+use crate::render::ShapeInstance;
+use crate::render::ShapeRenderer;
+use crate::render::SpriteInstance;
+use crate::render::SpriteRenderer;
 use crate::window::GameWindow;
+use crate::world::LineId;
 
 pub struct GameRenderer {
 	pub bits: GameRendererBits,
 	pub polyline_renderer: PolylineRenderer,
+	/// Entities, which share their shapes with lots of others.
+	pub shape_renderer: ShapeRenderer,
+	/// Pictures, under the lines.
+	pub sprite_renderer: SpriteRenderer,
+	/// The map's own lines.
 	pub background_line_buffer: PolylineBuffer,
+	/// One per streamed chunk, keyed by chunk index. Made when a chunk shows up and thrown away when it's dropped.
+	pub chunk_line_buffers: HashMap<i64, PolylineBuffer>,
+	/// Every world line, tessellated, along with the chunk it belongs to (`None` for the map's own lines). When lines
+	/// change, only those get tessellated again, and only the buffers they're in get uploaded again.
+	line_meshes: BTreeMap<LineId, (Option<i64>, LineMesh)>,
+	/// Re-tessellated every frame, since ropes move around.
+	pub entity_line_buffer: PolylineBuffer,
+	/// Whatever the game drew with `game.debug` last tick, over everything else.
+	pub debug_line_buffer: PolylineBuffer,
+	pub camera: Camera,
+	pub minimap: Minimap,
+	/// The scene gets drawn into this, and it takes it the rest of the way to the screen.
+	pub post_process: PostProcess,
+	/// Screenshots and frame sequences.
+	pub capture: Capture,
+	/// When the last frame was drawn, for moving the camera smoothly between ticks.
+	last_frame: Instant,
 }
 
 impl GameRenderer {
+	/// How far ahead the aim guide looks, in maps with gravity.
+	const AIM_GUIDE_TICKS: u32 = 180;
+
 	pub async fn new(game_window: &GameWindow, asset_loader: &AssetLoader) -> Result<GameRenderer> {
 		let bits = GameRendererBits::new(game_window).await?;
 
 		let polyline_renderer = PolylineRenderer::new(&bits, asset_loader)?;
+		let shape_renderer = ShapeRenderer::new(&bits, asset_loader)?;
+		let sprite_renderer = SpriteRenderer::new(&bits, asset_loader)?;
 		let background_line_buffer = polyline_renderer.make_buffers(&bits.device);
-
-		Ok(GameRenderer { bits, polyline_renderer, background_line_buffer })
+		let entity_line_buffer = polyline_renderer.make_buffers(&bits.device);
+		let debug_line_buffer = polyline_renderer.make_buffers(&bits.device);
+
+		let camera = Camera::new(bits.size, CameraSettings::load(asset_loader)?);
+		let minimap = Minimap::new(&bits, &polyline_renderer, MinimapSettings::load(asset_loader)?);
+		let post_process = PostProcess::new(&bits, asset_loader, PostProcessSettings::load(asset_loader)?)?;
+
+		Ok(GameRenderer {
+			bits,
+			polyline_renderer,
+			shape_renderer,
+			sprite_renderer,
+			background_line_buffer,
+			chunk_line_buffers: HashMap::new(),
+			line_meshes: BTreeMap::new(),
+			entity_line_buffer,
+			debug_line_buffer,
+			camera,
+			minimap,
+			post_process,
+			capture: Capture::new("screenshots"),
+			last_frame: Instant::now(),
+		})
 	}
 
 	pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
-		self.bits.resize(new_size)
+		self.bits.resize(new_size);
+		self.post_process.resize(&self.bits, new_size);
 	}
 
 	pub fn recreate_swap_chain(&mut self) {
 		self.bits.recreate_swap_chain()
 	}
 
-	pub fn setup(&mut self, game: &Game) {
-		self.background_line_buffer.tessellate(&self.bits.queue, &game.world.lines);
+	pub fn setup(&mut self, game: &mut Game) {
+		game.world.lines.take_changed();
+		self.line_meshes = game.world.lines.iter().map(|(id, line)| (id, (game.world.chunk_of(id), LineMesh::tessellate(line)))).collect();
+
+		self.chunk_line_buffers.clear();
+		let mut groups: BTreeSet<Option<i64>> = self.line_meshes.values().map(|(group, _)| *group).collect();
+		groups.insert(None);
+		for group in groups {
+			self.upload_lines(group);
+		}
+
+		self.camera.world_bounds = game.world.lines.bounds();
+		if let Some(focus) = game.world.focus() {
+			self.camera.snap_to(focus, self.bits.size);
+		}
+	}
+
+	/// Catches up with lines that got added or removed since last frame, whether that's chunks streaming in and out or
+	/// bits getting blown off.
+	fn update_lines(&mut self, game: &mut Game) {
+		let mut dirty = BTreeSet::new();
+		for id in game.world.lines.take_changed() {
+			if let Some((group, _)) = self.line_meshes.remove(&id) {
+				dirty.insert(group);
+			}
+			if let Some(line) = game.world.lines.get(id) {
+				let group = game.world.chunk_of(id);
+				self.line_meshes.insert(id, (group, LineMesh::tessellate(line)));
+				dirty.insert(group);
+			}
+		}
+
+		if !dirty.is_empty() {
+			self.camera.world_bounds = game.world.lines.bounds();
+		}
+		for group in dirty {
+			self.upload_lines(group);
+		}
+	}
+
+	/// Refills the buffer for the map's lines (`None`) or a chunk's lines from the cached meshes.
+	fn upload_lines(&mut self, group: Option<i64>) {
+		let GameRendererBits { device, queue, .. } = &self.bits;
+		let meshes: Vec<&LineMesh> = self.line_meshes.values().filter(|(g, _)| *g == group).map(|(_, mesh)| mesh).collect();
+
+		match group {
+			None => self.background_line_buffer.upload(device, queue, meshes),
+			Some(index) if meshes.is_empty() => {
+				self.chunk_line_buffers.remove(&index);
+			},
+			Some(index) => {
+				let polyline_renderer = &self.polyline_renderer;
+				let buffer = self.chunk_line_buffers.entry(index).or_insert_with(|| polyline_renderer.make_buffers(device));
+				buffer.upload(device, queue, meshes);
+			},
+		}
+	}
+
+	pub fn screen_to_world(&self, screen: ultraviolet::Vec2) -> ultraviolet::Vec2 {
+		self.camera.screen_to_world(screen, self.bits.size)
 	}
 
-	pub fn render(&mut self, _game: &mut Game) -> std::result::Result<(), SwapChainError> {
+	pub fn render(&mut self, game: &mut Game) -> std::result::Result<(), SwapChainError> {
 		let frame = self.bits.sc.get_current_frame()?.output;
 		let mut encoder = self.bits.device.create_command_encoder(&CommandEncoderDescriptor { label: None });
 
+		self.update_lines(game);
+		for decal in &game.world.decals {
+			self.sprite_renderer.draw(&decal.texture, SpriteInstance::new(&decal.transform(), decal.size, decal.uv, decal.tint));
+		}
+		self.sprite_renderer.prepare(&self.bits);
+
+		let entities = &game.world.entities;
+		let instances = entities
+			.shapes
+			.iter()
+			.filter_map(|(id, shape)| entities.transforms.get(id).map(|transform| (shape, ShapeInstance::new(transform, ultraviolet::Vec3::one()))));
+		self.shape_renderer.prepare(&self.bits.device, &self.bits.queue, instances);
+
+		let mut moving_lines: Vec<_> = game.world.ropes.iter().map(|rope| rope.polyline()).collect();
+		if !game.world.gravity.is_empty() {
+			//aim guide, so orbits can be lined up
+			if let Some(mut guide) = game.predict_player(Self::AIM_GUIDE_TICKS).filter(|guide| guide.points.len() >= 2) {
+				guide.color = ultraviolet::Vec3::new(0.4, 0.4, 0.5);
+				guide.thickness = 1.5;
+				moving_lines.push(guide);
+			}
+		}
+		self.entity_line_buffer.tessellate(&self.bits.device, &self.bits.queue, &moving_lines);
+		if debug_draw::is_enabled() {
+			self.debug_line_buffer.tessellate(&self.bits.device, &self.bits.queue, game.debug.shapes());
+		}
+
+		let now = Instant::now();
+		let dt = (now - self.last_frame).as_secs_f32();
+		self.last_frame = now;
+		let focus = game.world.focus().map(|position| (position, game.world.focus_velocity()));
+		self.camera.update(focus, self.bits.size, dt);
+		self.bits.uniforms.view = self.camera.view_matrix(self.bits.size).into();
+		self.bits.uniforms.beam = self.post_process.beam();
+		self.minimap.update(&self.bits, game);
+
 		//write uniforms (doesn't reallllly need to happen every frame, practically speaking it will, no harm)
 		self.bits.queue.write_buffer(&self.bits.uniform_buffer, 0, bytemuck::cast_slice(&[self.bits.uniforms]));
 
 		let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
 			label: Some("pass!"),
 			color_attachments: &[RenderPassColorAttachmentDescriptor {
-				attachment: &frame.view,
+				attachment: self.post_process.scene_view(),
 				resolve_target: None,
 				ops: Operations { load: LoadOp::Clear(Color { r: 1.0, g: 0.5, b: 0.1, a: 1.0 }), store: true },
 			}],
@@ -57,12 +219,34 @@ impl GameRenderer {
 		// apply global uniforms
 		pass.set_bind_group(0, &self.bits.uniform_bind_group, &[]);
 
-		//render the background of the scene
+		//render the background of the scene, pictures first
+		self.sprite_renderer.render(&mut pass);
 		self.polyline_renderer.render_buffers(&mut pass, &self.background_line_buffer);
+		for buffer in self.chunk_line_buffers.values() {
+			self.polyline_renderer.render_buffers(&mut pass, buffer);
+		}
 
-		//all done. submit to the gpu
+		//and everything that moves on top
+		self.shape_renderer.render(&mut pass);
+		self.polyline_renderer.render_buffers(&mut pass, &self.entity_line_buffer);
+		if debug_draw::is_enabled() {
+			self.polyline_renderer.render_buffers(&mut pass, &self.debug_line_buffer);
+		}
+
+		//minimap in the corner, drawn from the same world line buffers
+		let world_buffers = std::iter::once(&self.background_line_buffer).chain(self.chunk_line_buffers.values());
+		self.minimap.render(&mut pass, &self.polyline_renderer, world_buffers, self.bits.size);
+
+		//glow, tonemapping, and onto the screen (and into the capture target, if something's being captured)
 		drop(pass);
+		let mut outputs = vec![&frame.view];
+		outputs.extend(self.capture.begin_frame(&self.bits));
+		self.post_process.render(&self.bits.queue, &mut encoder, &outputs, dt);
+		self.capture.copy_frame(&mut encoder);
+
+		//all done. submit to the gpu
 		self.bits.queue.submit(std::iter::once(encoder.finish()));
+		self.capture.finish_frame(&self.bits);
 		Ok(())
 	}
 }
@@ -103,7 +287,7 @@ impl GameRendererBits {
 			format: adapter.get_swap_chain_preferred_format(&surface),
 			width: size.width,
 			height: size.height,
-			present_mode: PresentMode::Fifo,
+			present_mode: if game_window.vsync { PresentMode::Fifo } else { PresentMode::Immediate },
 		};
 
 		let sc = device.create_swap_chain(&surface, &sc_desc);
@@ -120,7 +304,7 @@ impl GameRendererBits {
 			label: Some("Rendererbits uniform bind layout"),
 			entries: &[BindGroupLayoutEntry {
 				binding: 0,
-				visibility: ShaderStage::VERTEX,
+				visibility: ShaderStage::VERTEX | ShaderStage::FRAGMENT,
 				ty: BindingType::Buffer { ty: BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
 				count: None,
 			}],
@@ -156,15 +340,20 @@ impl GameRendererBits {
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Uniforms {
 	pub pixel_to_ndc: [[f32; 4]; 4],
+	/// World to pixel coordinates, from the camera.
+	pub view: [[f32; 4]; 4],
+	/// How lines get drawn on a vector display. See `PostProcess::beam`.
+	pub beam: [f32; 4],
 }
 
 impl Uniforms {
-	fn new(size: PhysicalSize<u32>) -> Self {
+	/// Maps pixels in a target this big to NDC, with no camera.
+	pub fn new(size: PhysicalSize<u32>) -> Self {
 		use ultraviolet::projection::*;
 
 		//Left, right, bottom, top, near, far
 		let mat = orthographic_wgpu_dx(0.0, size.width as f32, size.height as f32, 0.0, -1.0, 1.0);
-		Self { pixel_to_ndc: mat.into() }
+		Self { pixel_to_ndc: mat.into(), view: ultraviolet::Mat4::identity().into(), beam: [0.0; 4] }
 	}
 
 	fn update(&mut self, size: PhysicalSize<u32>) {
@@ -0,0 +1,191 @@
+use anyhow::*;
+use serde::Deserialize;
+use serde::Serialize;
+use ultraviolet::Mat4;
+use ultraviolet::Vec2;
+use ultraviolet::Vec3;
+use util::DeviceExt;
+use wgpu::*;
+use winit::dpi::PhysicalSize;
+
+use crate::asset_loader::AssetLoader;
+use crate::game::Game;
+use crate::render::GameRendererBits;
+use crate::render::PolylineBuffer;
+use crate::render::PolylineRenderer;
+use crate::render::Uniforms;
+use crate::world::exploration::Exploration;
+use crate::world::Polyline;
+
+/// How the minimap looks. Loaded from `render/minimap.ron`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MinimapSettings {
+	/// Width and height, in pixels.
+	pub size: u32,
+	/// Gap between the minimap and the top right corner of the window, in pixels.
+	pub margin: u32,
+	/// Pixels per world unit.
+	pub scale: f32,
+	/// In pixels, no matter the scale.
+	pub icon_size: f32,
+	pub frame_thickness: f32,
+	#[serde(with = "crate::util::NotVec3")]
+	pub backdrop_color: Vec3,
+	#[serde(with = "crate::util::NotVec3")]
+	pub explored_color: Vec3,
+	#[serde(with = "crate::util::NotVec3")]
+	pub frame_color: Vec3,
+	/// Entities get an icon in the color of the first of these they have the tag for. The rest don't show up.
+	pub icons: Vec<MinimapIcon>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MinimapIcon {
+	pub tag: String,
+	#[serde(with = "crate::util::NotVec3")]
+	pub color: Vec3,
+}
+
+impl MinimapSettings {
+	pub const FILE: &'static str = "render/minimap.ron";
+
+	pub fn load(asset_loader: &AssetLoader) -> Result<Self> {
+		let file = asset_loader.load_string(Self::FILE)?;
+		ron::from_str(&file).with_context(|| anyhow!("couldnt parse minimap settings"))
+	}
+}
+
+/// A little map in the corner, centered on the player. It draws the same world line buffers as the main view, just
+/// through its own viewport and its own uniforms, so the lines don't get tessellated twice.
+pub struct Minimap {
+	pub settings: MinimapSettings,
+	uniforms: Uniforms,
+	uniform_buffer: Buffer,
+	uniform_bind_group: BindGroup,
+	/// Backdrop and explored areas, under the world lines.
+	under_buffer: PolylineBuffer,
+	/// Entity icons and the frame, over the world lines.
+	over_buffer: PolylineBuffer,
+}
+
+impl Minimap {
+	pub fn new(bits: &GameRendererBits, polyline_renderer: &PolylineRenderer, settings: MinimapSettings) -> Self {
+		let uniforms = Uniforms::new(PhysicalSize::new(settings.size, settings.size));
+
+		let uniform_buffer = bits.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+			label: Some("Minimap uniform buffer"),
+			contents: bytemuck::cast_slice(&[uniforms]),
+			usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+		});
+
+		let uniform_bind_group = bits.device.create_bind_group(&BindGroupDescriptor {
+			label: Some("Minimap uniform binds"),
+			layout: &bits.uniform_bind_group_layout,
+			entries: &[BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() }],
+		});
+
+		Minimap {
+			settings,
+			uniforms,
+			uniform_buffer,
+			uniform_bind_group,
+			under_buffer: polyline_renderer.make_buffers(&bits.device),
+			over_buffer: polyline_renderer.make_buffers(&bits.device),
+		}
+	}
+
+	/// Where the minimap goes on the screen, in pixels: x, y, width, height. `None` if the window is too small for it.
+	fn rect(&self, size: PhysicalSize<u32>) -> Option<(u32, u32, u32, u32)> {
+		let MinimapSettings { size: side, margin, .. } = self.settings;
+		if size.width < side + margin * 2 || size.height < side + margin * 2 {
+			return None;
+		}
+		Some((size.width - side - margin, margin, side, side))
+	}
+
+	/// Centers the minimap on the player and rebuilds the bits that aren't world lines.
+	pub fn update(&mut self, bits: &GameRendererBits, game: &Game) {
+		let center = match game.world.focus() {
+			Some(focus) => focus,
+			None => return,
+		};
+		let s = &self.settings;
+		let half_extent = Vec2::broadcast(s.size as f32 / 2.0 / s.scale);
+		let (min, max) = (center - half_extent, center + half_extent);
+
+		//one fat line over the whole thing for the backdrop, then explored cells in vertical runs, one per column
+		let mut under = vec![Polyline::new(vec![Vec2::new(min.x, center.y), Vec2::new(max.x, center.y)], s.backdrop_color, half_extent.y * 2.0)];
+		let mut run: Option<(i32, i32, i32)> = None;
+		let flush = |run: Option<(i32, i32, i32)>, under: &mut Vec<Polyline>| {
+			if let Some((x, top, bottom)) = run {
+				let middle = Exploration::origin_of((x, top)).x + Exploration::CELL_SIZE / 2.0;
+				let (top, bottom) = (Exploration::origin_of((x, top)).y, Exploration::origin_of((x, bottom + 1)).y);
+				under.push(Polyline::new(vec![Vec2::new(middle, top), Vec2::new(middle, bottom)], s.explored_color, Exploration::CELL_SIZE));
+			}
+		};
+		for (x, y) in game.world.explored.cells_in(min, max) {
+			run = match run {
+				Some((run_x, top, bottom)) if run_x == x && bottom + 1 == y => Some((x, top, y)),
+				_ => {
+					flush(run, &mut under);
+					Some((x, y, y))
+				},
+			};
+		}
+		flush(run, &mut under);
+
+		let entities = &game.world.entities;
+		let icon_radius = s.icon_size / 2.0 / s.scale;
+		let mut over: Vec<Polyline> = entities
+			.ids()
+			.filter_map(|id| {
+				let icon = s.icons.iter().find(|icon| entities.has_tag(id, &icon.tag))?;
+				let position = entities.transforms.get(id)?.position;
+				let diamond = [(0.0, -1.0), (1.0, 0.0), (0.0, 1.0), (-1.0, 0.0), (0.0, -1.0)];
+				let points = diamond.iter().map(|&(x, y)| position + Vec2::new(x, y) * icon_radius).collect();
+				Some(Polyline::new(points, icon.color, icon_radius))
+			})
+			.collect();
+
+		let inset = s.frame_thickness / 2.0 / s.scale;
+		let (frame_min, frame_max) = (min + Vec2::broadcast(inset), max - Vec2::broadcast(inset));
+		let corners = vec![frame_min, Vec2::new(frame_max.x, frame_min.y), frame_max, Vec2::new(frame_min.x, frame_max.y), frame_min];
+		over.push(Polyline::new(corners, s.frame_color, s.frame_thickness / s.scale));
+
+		self.under_buffer.tessellate(&bits.device, &bits.queue, &under);
+		self.over_buffer.tessellate(&bits.device, &bits.queue, &over);
+
+		//scale the world down around the player, into the minimap's own pixels
+		let middle = s.size as f32 / 2.0;
+		let view = Mat4::from_translation(Vec3::new(middle, middle, 0.0))
+			* Mat4::from_nonuniform_scale(Vec3::new(s.scale, s.scale, 1.0))
+			* Mat4::from_translation(Vec3::new(-center.x, -center.y, 0.0));
+		self.uniforms.view = view.into();
+		bits.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[self.uniforms]));
+	}
+
+	/// Draws the minimap in its corner, with `world_buffers` (already tessellated world lines) inside it. Leaves the
+	/// pass's viewport, scissor and uniforms set for the minimap, so draw it last.
+	pub fn render<'a>(
+		&'a self,
+		pass: &mut RenderPass<'a>,
+		polyline_renderer: &'a PolylineRenderer,
+		world_buffers: impl IntoIterator<Item = &'a PolylineBuffer>,
+		size: PhysicalSize<u32>,
+	) {
+		let (x, y, width, height) = match self.rect(size) {
+			Some(rect) => rect,
+			None => return,
+		};
+
+		pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+		pass.set_scissor_rect(x, y, width, height);
+		pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+
+		polyline_renderer.render_buffers(pass, &self.under_buffer);
+		for buffer in world_buffers {
+			polyline_renderer.render_buffers(pass, buffer);
+		}
+		polyline_renderer.render_buffers(pass, &self.over_buffer);
+	}
+}
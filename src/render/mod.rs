@@ -1,5 +1,19 @@
+mod atlas;
+mod camera;
+mod capture;
 mod game_renderer;
+mod minimap;
 mod polyline_renderer;
+mod post_process;
+mod shape_renderer;
+mod sprite_renderer;
 
+pub use atlas::*;
+pub use camera::*;
+pub use capture::*;
 pub use game_renderer::*;
+pub use minimap::*;
 pub use polyline_renderer::*;
+pub use post_process::*;
+pub use shape_renderer::*;
+pub use sprite_renderer::*;
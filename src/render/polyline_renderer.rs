@@ -3,6 +3,7 @@ use util::DeviceExt;
 use wgpu::*;
 
 use super::GameRendererBits;
+use super::PostProcess;
 use crate::asset_loader::AssetLoader;
 use crate::world::Polyline;
 
@@ -37,7 +38,7 @@ impl PolylineRenderer {
 			label: Some("Line pipeline"),
 			layout: Some(&pipeline_layout),
 			vertex: VertexState { module: &vert_module, entry_point: "main", buffers: &[buffer_layout] },
-			fragment: Some(FragmentState { module: &frag_module, entry_point: "main", targets: &[game_renderer.sc_desc.format.into()] }),
+			fragment: Some(FragmentState { module: &frag_module, entry_point: "main", targets: &[PostProcess::SCENE_FORMAT.into()] }),
 			primitive: PrimitiveState {
 				cull_mode: CullMode::None, //For now, until i get things debugged
 				..Default::default()
@@ -51,7 +52,7 @@ impl PolylineRenderer {
 
 	pub fn make_buffers(&self, device: &Device) -> PolylineBuffer {
 		//Do you need to zero out buffers? Idk seems like a good idea
-		let buncha_zeroes = vec![0; Self::BUFFER_SIZE];
+		let buncha_zeroes = vec![0u8; Self::BUFFER_SIZE];
 
 		let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
 			label: Some("Line vertex buffer"),
@@ -65,7 +66,7 @@ impl PolylineRenderer {
 			usage: BufferUsage::COPY_DST | BufferUsage::INDEX,
 		});
 
-		PolylineBuffer { vertex_buffer, index_buffer, index_count: 0 }
+		PolylineBuffer { vertex_buffer, index_buffer, index_count: 0, vertex_capacity: Self::BUFFER_SIZE, index_capacity: Self::BUFFER_SIZE }
 	}
 
 	/// Assumes bind group 0 is global uniforms
@@ -81,69 +82,119 @@ pub struct PolylineBuffer {
 	vertex_buffer: Buffer,
 	index_buffer: Buffer,
 	index_count: u32,
+	/// Sizes of the buffers, in bytes. They get swapped for bigger ones when there's too much to fit.
+	vertex_capacity: usize,
+	index_capacity: usize,
 }
 
 impl PolylineBuffer {
-	pub fn tessellate<'a>(&'a mut self, queue: &Queue, polylines: &[Polyline]) {
+	pub fn tessellate<'a>(&'a mut self, device: &Device, queue: &Queue, polylines: &[Polyline]) {
+		let meshes: Vec<LineMesh> = polylines.iter().map(LineMesh::tessellate).collect();
+		self.upload(device, queue, &meshes);
+	}
+
+	/// Fills the buffers with already tessellated lines.
+	pub fn upload<'m>(&mut self, device: &Device, queue: &Queue, meshes: impl IntoIterator<Item = &'m LineMesh>) {
 		let mut vertices: Vec<Vert> = Vec::new();
 		let mut indices: Vec<u32> = Vec::new();
 
-		for polyline in polylines {
-			//Unfortunately I need a new path builder for each polyline
-			//lyon doesn't support setting the thickness or color per-stroke, as far as I can tell??
-			let mut path_builder = lyon::path::Path::builder();
-
-			let mut point_iter = polyline.points.iter();
-			let first = point_iter.next().expect("empty polyline?");
-			path_builder.begin(lyon::geom::point(first.x, first.y));
-			for next in point_iter {
-				path_builder.line_to(lyon::geom::point(next.x, next.y));
-			}
-			path_builder.end(false); //no close
-
-			let path = path_builder.build();
-
-			use lyon::lyon_tessellation::*;
-
-			let mut tess_out: VertexBuffers<Vert, u16> = VertexBuffers::new();
-			let mut tess = StrokeTessellator::new();
-			{
-				tess.tessellate_path(
-					&path,
-					&StrokeOptions::default()
-						.with_line_cap(LineCap::Butt)
-						.with_line_join(LineJoin::Miter)
-						.with_miter_limit(500.0)
-						.with_line_width(polyline.thickness),
-					&mut BuffersBuilder::new(&mut tess_out, |pos: StrokeVertex| Vert { position: pos.position().to_array(), color: polyline.color.into() }),
-				)
-				.expect("failed to tesselate");
-			}
-
+		for mesh in meshes {
 			//since i'll be shoving these into the same buffer, adjust the index buffer to point here
-			//also map to u32 incase there's as hitton of lines (doubt it)
 			let vert_count = vertices.len() as u32;
-			let indices_u32 = tess_out.indices.into_iter().map(|x| (x as u32) + vert_count).collect::<Vec<_>>();
-
-			vertices.extend_from_slice(&tess_out.vertices);
-			indices.extend_from_slice(&indices_u32);
+			vertices.extend_from_slice(&mesh.vertices);
+			indices.extend(mesh.indices.iter().map(|x| x + vert_count));
 		}
 
 		//great now fill the buffers on the GPU
-		queue.write_buffer(&self.vertex_buffer, 0, &bytemuck::cast_slice(&vertices));
-		queue.write_buffer(&self.index_buffer, 0, &bytemuck::cast_slice(&indices));
 		self.index_count = indices.len() as u32;
+		if indices.is_empty() {
+			return;
+		}
+
+		let vertex_bytes: &[u8] = bytemuck::cast_slice(&vertices);
+		let index_bytes: &[u8] = bytemuck::cast_slice(&indices);
+		if vertex_bytes.len() > self.vertex_capacity {
+			self.vertex_capacity = vertex_bytes.len().next_power_of_two();
+			self.vertex_buffer = Self::make_buffer(device, "Line vertex buffer", self.vertex_capacity, BufferUsage::COPY_DST | BufferUsage::VERTEX);
+		}
+		if index_bytes.len() > self.index_capacity {
+			self.index_capacity = index_bytes.len().next_power_of_two();
+			self.index_buffer = Self::make_buffer(device, "Line index buffer", self.index_capacity, BufferUsage::COPY_DST | BufferUsage::INDEX);
+		}
+
+		queue.write_buffer(&self.vertex_buffer, 0, vertex_bytes);
+		queue.write_buffer(&self.index_buffer, 0, index_bytes);
+	}
+
+	pub(super) fn make_buffer(device: &Device, label: &str, size: usize, usage: BufferUsage) -> Buffer {
+		device.create_buffer(&BufferDescriptor { label: Some(label), size: size as BufferAddress, usage, mapped_at_creation: false })
+	}
+}
+
+/// The triangles for one polyline. Worth keeping around for lines that don't change much, since uploading is a lot
+/// cheaper than tessellating.
+#[derive(Default)]
+pub struct LineMesh {
+	pub(super) vertices: Vec<Vert>,
+	pub(super) indices: Vec<u32>,
+}
+
+impl LineMesh {
+	pub fn tessellate(polyline: &Polyline) -> Self {
+		//Unfortunately I need a new path builder for each polyline
+		//lyon doesn't support setting the thickness or color per-stroke, as far as I can tell??
+		let mut path_builder = lyon::path::Path::builder();
+
+		let mut point_iter = polyline.points.iter();
+		let first = point_iter.next().expect("empty polyline?");
+		path_builder.begin(lyon::geom::point(first.x, first.y));
+		for next in point_iter {
+			path_builder.line_to(lyon::geom::point(next.x, next.y));
+		}
+		path_builder.end(false); //no close
+
+		let path = path_builder.build();
+
+		use lyon::lyon_tessellation::*;
+
+		let length = polyline.length();
+		let mut tess_out: VertexBuffers<Vert, u16> = VertexBuffers::new();
+		let mut tess = StrokeTessellator::new();
+		{
+			tess.tessellate_path(
+				&path,
+				&StrokeOptions::default()
+					.with_line_cap(LineCap::Butt)
+					.with_line_join(LineJoin::Miter)
+					.with_miter_limit(500.0)
+					.with_line_width(polyline.thickness),
+				&mut BuffersBuilder::new(&mut tess_out, |pos: StrokeVertex| {
+					let across = match pos.side() {
+						Side::Left => -1.0,
+						Side::Right => 1.0,
+					};
+					Vert { position: pos.position().to_array(), color: polyline.color.into(), beam: [across, pos.advancement(), length - pos.advancement()] }
+				}),
+			)
+			.expect("failed to tesselate");
+		}
+
+		//map to u32 incase there's as hitton of lines (doubt it)
+		LineMesh { vertices: tess_out.vertices, indices: tess_out.indices.into_iter().map(|x| x as u32).collect() }
 	}
 }
 
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 #[repr(C)]
-struct Vert {
+pub(super) struct Vert {
 	position: [f32; 2],
 	color: [f32; 3],
+	/// Where this is on the line, for vector display effects: -1 to 1 across it, then the distance from the start and
+	/// the distance to the end.
+	beam: [f32; 3],
 }
 
 impl Vert {
 	#[allow(dead_code)] //no, it's used, r-a
-	pub const LAYOUT: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![0 => Float2, 1 => Float3];
+	pub const LAYOUT: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![0 => Float2, 1 => Float3, 2 => Float3];
 }
@@ -0,0 +1,407 @@
+use std::collections::HashMap;
+
+use anyhow::*;
+use serde::Deserialize;
+use serde::Serialize;
+use ultraviolet::Vec3;
+use util::DeviceExt;
+use wgpu::*;
+use winit::dpi::PhysicalSize;
+
+use crate::asset_loader::AssetLoader;
+use crate::render::GameRendererBits;
+
+/// How the scene gets turned into what ends up on screen. Loaded from `render/post_process.ron`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PostProcessSettings {
+	pub threshold: f32,
+	pub knee: f32,
+	pub bloom_strength: f32,
+	/// Glow is blurred at the window size divided by this.
+	pub downsample: u32,
+	pub blur_passes: u32,
+	pub exposure: f32,
+	pub tonemap: Tonemap,
+	/// Name of a preset in `render/crt_presets.ron`.
+	#[serde(default)]
+	pub crt: Option<String>,
+}
+
+impl PostProcessSettings {
+	pub const FILE: &'static str = "render/post_process.ron";
+
+	pub fn load(asset_loader: &AssetLoader) -> Result<Self> {
+		let file = asset_loader.load_string(Self::FILE)?;
+		ron::from_str(&file).with_context(|| anyhow!("couldnt parse post process settings"))
+	}
+}
+
+/// What makes it look like an old vector monitor.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CrtSettings {
+	/// How much of its brightness the phosphor keeps each 60th of a second.
+	pub persistence: f32,
+	pub beam_falloff: f32,
+	/// In world units.
+	pub end_dwell: f32,
+	pub end_brightness: f32,
+	pub scanlines: f32,
+	pub scanline_spacing: f32,
+	pub curvature: f32,
+	pub vignette: f32,
+	pub flicker: f32,
+	#[serde(with = "crate::util::NotVec3")]
+	pub phosphor: Vec3,
+	pub monochrome: f32,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CrtPresets(pub HashMap<String, CrtSettings>);
+
+impl CrtPresets {
+	pub const FILE: &'static str = "render/crt_presets.ron";
+
+	pub fn load(asset_loader: &AssetLoader) -> Result<Self> {
+		let file = asset_loader.load_string(Self::FILE)?;
+		ron::from_str(&file).with_context(|| anyhow!("couldnt parse crt presets"))
+	}
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum Tonemap {
+	None,
+	Reinhard,
+	Aces,
+}
+
+/// Matches `glsl_shaders/post_uniforms.glsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostUniforms {
+	texel_size: [f32; 2],
+	direction: [f32; 2],
+	threshold: f32,
+	knee: f32,
+	bloom_strength: f32,
+	exposure: f32,
+	tonemap: u32,
+	persistence: f32,
+	flicker: f32,
+	scanlines: f32,
+	scanline_spacing: f32,
+	curvature: f32,
+	vignette: f32,
+	monochrome: f32,
+	phosphor: [f32; 3],
+	crt: u32,
+}
+
+/// Scene -> bright pass -> blur -> composite + tonemap. With a CRT preset there's a phosphor history pass first.
+pub struct PostProcess {
+	pub settings: PostProcessSettings,
+	pub crt: Option<CrtSettings>,
+	binder: PostBinder,
+	persist_pipeline: RenderPipeline,
+	bright_pipeline: RenderPipeline,
+	blur_pipeline: RenderPipeline,
+	composite_pipeline: RenderPipeline,
+	targets: PostTargets,
+	frame: usize,
+}
+
+struct PostBinder {
+	layout: BindGroupLayout,
+	sampler: Sampler,
+}
+
+struct PostTargets {
+	scene: TextureView,
+	/// Ping-ponged each frame, with a CRT preset.
+	history: Vec<TextureView>,
+	bloom: [TextureView; 2],
+	frames: Vec<FrameBinds>,
+	blur: [PassBinds; 2],
+}
+
+struct FrameBinds {
+	persist: Option<PassBinds>,
+	bright: PassBinds,
+	composite: PassBinds,
+}
+
+struct PassBinds {
+	uniforms: PostUniforms,
+	uniform_buffer: Buffer,
+	bind_group: BindGroup,
+}
+
+impl PassBinds {
+	fn update(&self, queue: &Queue, change: impl FnOnce(&mut PostUniforms)) {
+		let mut uniforms = self.uniforms;
+		change(&mut uniforms);
+		queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+	}
+}
+
+impl PostProcess {
+	/// What the scene gets drawn into. Anything that draws the scene needs its pipeline to target this.
+	pub const SCENE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+	pub fn new(bits: &GameRendererBits, asset_loader: &AssetLoader, settings: PostProcessSettings) -> Result<Self> {
+		let device = &bits.device;
+
+		let crt = match &settings.crt {
+			Some(name) => {
+				let mut presets = CrtPresets::load(asset_loader)?;
+				Some(presets.0.remove(name).ok_or_else(|| anyhow!("no crt preset called '{}'", name))?)
+			},
+			None => None,
+		};
+
+		let texture_entry = |binding| BindGroupLayoutEntry {
+			binding,
+			visibility: ShaderStage::FRAGMENT,
+			ty: BindingType::Texture {
+				sample_type: TextureSampleType::Float { filterable: true },
+				view_dimension: TextureViewDimension::D2,
+				multisampled: false,
+			},
+			count: None,
+		};
+		let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+			label: Some("Post process bind layout"),
+			entries: &[
+				texture_entry(0),
+				BindGroupLayoutEntry {
+					binding: 1,
+					visibility: ShaderStage::FRAGMENT,
+					ty: BindingType::Sampler { filtering: true, comparison: false },
+					count: None,
+				},
+				BindGroupLayoutEntry {
+					binding: 2,
+					visibility: ShaderStage::FRAGMENT,
+					ty: BindingType::Buffer { ty: BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
+					count: None,
+				},
+				//only some passes read this one (the bloom, or the phosphor history). the others get handed something,
+				//since the layout is shared
+				texture_entry(3),
+			],
+		});
+
+		let sampler = device.create_sampler(&SamplerDescriptor {
+			label: Some("Post process sampler"),
+			address_mode_u: AddressMode::ClampToEdge,
+			address_mode_v: AddressMode::ClampToEdge,
+			mag_filter: FilterMode::Linear,
+			min_filter: FilterMode::Linear,
+			..Default::default()
+		});
+
+		let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+			label: Some("Post process pipeline layout"),
+			bind_group_layouts: &[&layout],
+			push_constant_ranges: &[],
+		});
+		let vert_module = asset_loader.create_shader_module(device, "fullscreen.vert.spv").context("failed to load fullscreen vert shader")?;
+		let pipeline = |frag: &str, format: TextureFormat| -> Result<RenderPipeline> {
+			let frag_module = asset_loader.create_shader_module(device, frag).with_context(|| format!("failed to load post process shader '{}'", frag))?;
+			Ok(device.create_render_pipeline(&RenderPipelineDescriptor {
+				label: Some(frag),
+				layout: Some(&pipeline_layout),
+				vertex: VertexState { module: &vert_module, entry_point: "main", buffers: &[] },
+				fragment: Some(FragmentState { module: &frag_module, entry_point: "main", targets: &[format.into()] }),
+				primitive: Default::default(),
+				depth_stencil: None,
+				multisample: Default::default(),
+			}))
+		};
+
+		let persist_pipeline = pipeline("persist.frag.spv", Self::SCENE_FORMAT)?;
+		let bright_pipeline = pipeline("bright_pass.frag.spv", Self::SCENE_FORMAT)?;
+		let blur_pipeline = pipeline("blur.frag.spv", Self::SCENE_FORMAT)?;
+		let composite_pipeline = pipeline("composite.frag.spv", bits.sc_desc.format)?;
+
+		let binder = PostBinder { layout, sampler };
+		let targets = PostTargets::new(bits, &binder, &settings, crt.as_ref(), bits.size);
+
+		Ok(PostProcess { settings, crt, binder, persist_pipeline, bright_pipeline, blur_pipeline, composite_pipeline, targets, frame: 0 })
+	}
+
+	pub fn resize(&mut self, bits: &GameRendererBits, size: PhysicalSize<u32>) {
+		self.targets = PostTargets::new(bits, &self.binder, &self.settings, self.crt.as_ref(), size);
+	}
+
+	pub fn scene_view(&self) -> &TextureView {
+		&self.targets.scene
+	}
+
+	/// Beam falloff, end dwell and end brightness for the line shader. All zeroes for plain lines.
+	pub fn beam(&self) -> [f32; 4] {
+		match &self.crt {
+			Some(crt) => [crt.beam_falloff, crt.end_dwell, crt.end_brightness, 0.0],
+			None => [0.0; 4],
+		}
+	}
+
+	/// `dt` is in seconds.
+	pub fn render(&mut self, queue: &Queue, encoder: &mut CommandEncoder, outputs: &[&TextureView], dt: f32) {
+		let targets = &self.targets;
+		self.frame = self.frame.wrapping_add(1);
+		let current = self.frame % targets.frames.len();
+		let binds = &targets.frames[current];
+
+		if let (Some(crt), Some(persist)) = (&self.crt, &binds.persist) {
+			//the same trails at any frame rate
+			persist.update(queue, |u| u.persistence = crt.persistence.powf(dt * 60.0));
+			Self::pass(encoder, &targets.history[current], &self.persist_pipeline, persist);
+
+			let flicker = 1.0 - crt.flicker * rand::random::<f32>();
+			binds.composite.update(queue, |u| u.flicker = flicker);
+		}
+
+		Self::pass(encoder, &targets.bloom[0], &self.bright_pipeline, &binds.bright);
+		for _ in 0..self.settings.blur_passes {
+			Self::pass(encoder, &targets.bloom[1], &self.blur_pipeline, &targets.blur[0]);
+			Self::pass(encoder, &targets.bloom[0], &self.blur_pipeline, &targets.blur[1]);
+		}
+		for output in outputs {
+			Self::pass(encoder, output, &self.composite_pipeline, &binds.composite);
+		}
+	}
+
+	fn pass(encoder: &mut CommandEncoder, target: &TextureView, pipeline: &RenderPipeline, binds: &PassBinds) {
+		let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+			label: Some("Post process pass"),
+			color_attachments: &[RenderPassColorAttachmentDescriptor {
+				attachment: target,
+				resolve_target: None,
+				ops: Operations { load: LoadOp::Clear(Color::BLACK), store: true },
+			}],
+			depth_stencil_attachment: None,
+		});
+		pass.set_pipeline(pipeline);
+		pass.set_bind_group(0, &binds.bind_group, &[]);
+		pass.draw(0..3, 0..1);
+	}
+}
+
+impl PostBinder {
+	fn binds(&self, device: &Device, source: &TextureView, extra: &TextureView, uniforms: PostUniforms) -> PassBinds {
+		let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+			label: Some("Post process uniform buffer"),
+			contents: bytemuck::cast_slice(&[uniforms]),
+			usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+		});
+		let bind_group = device.create_bind_group(&BindGroupDescriptor {
+			label: Some("Post process binds"),
+			layout: &self.layout,
+			entries: &[
+				BindGroupEntry { binding: 0, resource: BindingResource::TextureView(source) },
+				BindGroupEntry { binding: 1, resource: BindingResource::Sampler(&self.sampler) },
+				BindGroupEntry { binding: 2, resource: uniform_buffer.as_entire_binding() },
+				BindGroupEntry { binding: 3, resource: BindingResource::TextureView(extra) },
+			],
+		});
+		PassBinds { uniforms, uniform_buffer, bind_group }
+	}
+}
+
+impl PostTargets {
+	fn new(bits: &GameRendererBits, binder: &PostBinder, settings: &PostProcessSettings, crt: Option<&CrtSettings>, size: PhysicalSize<u32>) -> Self {
+		let device = &bits.device;
+
+		//minimized windows are 0x0, and textures can't be
+		let (width, height) = (size.width.max(1), size.height.max(1));
+		let downsample = settings.downsample.max(1);
+		let (bloom_width, bloom_height) = ((width / downsample).max(1), (height / downsample).max(1));
+
+		let target = |label, width, height| {
+			device
+				.create_texture(&TextureDescriptor {
+					label: Some(label),
+					size: Extent3d { width, height, depth: 1 },
+					mip_level_count: 1,
+					sample_count: 1,
+					dimension: TextureDimension::D2,
+					format: PostProcess::SCENE_FORMAT,
+					usage: TextureUsage::RENDER_ATTACHMENT | TextureUsage::SAMPLED,
+				})
+				.create_view(&TextureViewDescriptor::default())
+		};
+		let scene = target("Scene target", width, height);
+		let bloom = [target("Bloom target 0", bloom_width, bloom_height), target("Bloom target 1", bloom_width, bloom_height)];
+		let history: Vec<TextureView> = match crt {
+			Some(_) => vec![target("Phosphor history 0", width, height), target("Phosphor history 1", width, height)],
+			None => Vec::new(),
+		};
+
+		//history starts out black, instead of whatever was in that memory before
+		let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: Some("Clear phosphor history") });
+		for view in &history {
+			encoder.begin_render_pass(&RenderPassDescriptor {
+				label: Some("Clear phosphor history"),
+				color_attachments: &[RenderPassColorAttachmentDescriptor {
+					attachment: view,
+					resolve_target: None,
+					ops: Operations { load: LoadOp::Clear(Color::BLACK), store: true },
+				}],
+				depth_stencil_attachment: None,
+			});
+		}
+		bits.queue.submit(std::iter::once(encoder.finish()));
+
+		let base = PostUniforms {
+			threshold: settings.threshold,
+			knee: settings.knee,
+			bloom_strength: settings.bloom_strength,
+			exposure: settings.exposure,
+			tonemap: settings.tonemap as u32,
+			flicker: 1.0,
+			..Default::default()
+		};
+		let base = match crt {
+			Some(crt) => PostUniforms {
+				scanlines: crt.scanlines,
+				scanline_spacing: crt.scanline_spacing,
+				curvature: crt.curvature,
+				vignette: crt.vignette,
+				monochrome: crt.monochrome,
+				phosphor: crt.phosphor.into(),
+				crt: 1,
+				..base
+			},
+			None => base,
+		};
+		let uniforms = |source_size: (u32, u32), direction: [f32; 2]| PostUniforms {
+			texel_size: [1.0 / source_size.0 as f32, 1.0 / source_size.1 as f32],
+			direction,
+			..base
+		};
+
+		let full_size = (width, height);
+		let bloom_size = (bloom_width, bloom_height);
+		let blur = [
+			binder.binds(device, &bloom[0], &bloom[0], uniforms(bloom_size, [1.0, 0.0])),
+			binder.binds(device, &bloom[1], &bloom[1], uniforms(bloom_size, [0.0, 1.0])),
+		];
+		let frame_binds = |source: &TextureView, persist: Option<PassBinds>| FrameBinds {
+			persist,
+			bright: binder.binds(device, source, source, uniforms(full_size, [0.0, 0.0])),
+			composite: binder.binds(device, source, &bloom[0], uniforms(full_size, [0.0, 0.0])),
+		};
+		let frames = match history.len() {
+			//each frame mixes the scene into one history texture, from the one before
+			2 => (0..2)
+				.map(|i| {
+					let persist = binder.binds(device, &scene, &history[1 - i], uniforms(full_size, [0.0, 0.0]));
+					frame_binds(&history[i], Some(persist))
+				})
+				.collect(),
+			_ => vec![frame_binds(&scene, None)],
+		};
+
+		PostTargets { scene, history, bloom, frames, blur }
+	}
+}
@@ -0,0 +1,234 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use anyhow::*;
+use ultraviolet::Rotor2;
+use ultraviolet::Vec2;
+use ultraviolet::Vec3;
+use wgpu::*;
+
+use super::GameRendererBits;
+use super::LineMesh;
+use super::PolylineBuffer;
+use super::PostProcess;
+use super::Vert;
+use crate::asset_loader::AssetLoader;
+use crate::world::Shape;
+use crate::world::ShapeKey;
+use crate::world::Transform;
+
+/// Instanced shapes. Each distinct shape is tessellated once into a shared mesh, copies are just instances.
+pub struct ShapeRenderer {
+	pipeline: RenderPipeline,
+	meshes: HashMap<ShapeKey, ShapeMesh>,
+	vertices: Vec<Vert>,
+	indices: Vec<u32>,
+	meshes_dirty: bool,
+	vertex_buffer: Buffer,
+	index_buffer: Buffer,
+	instance_buffer: Buffer,
+	/// In bytes.
+	vertex_capacity: usize,
+	index_capacity: usize,
+	instance_capacity: usize,
+	batches: Vec<(ShapeKey, Range<u32>)>,
+	frame: u64,
+}
+
+/// Indices count from the start of its own vertices.
+struct ShapeMesh {
+	indices: Range<u32>,
+	vertices: Range<u32>,
+	last_used: u64,
+}
+
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct ShapeInstance {
+	x_axis: [f32; 2],
+	y_axis: [f32; 2],
+	position: [f32; 2],
+	scale: f32,
+	tint: [f32; 3],
+}
+
+impl ShapeInstance {
+	#[allow(dead_code)] //no, it's used, r-a
+	pub const LAYOUT: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![3 => Float2, 4 => Float2, 5 => Float2, 6 => Float, 7 => Float3];
+
+	pub fn new(transform: &Transform, tint: Vec3) -> Self {
+		let rotor = Rotor2::from_angle(transform.rotation);
+		let (mut x_axis, mut y_axis) = (Vec2::unit_x() * transform.scale, Vec2::unit_y() * transform.scale);
+		rotor.rotate_vec(&mut x_axis);
+		rotor.rotate_vec(&mut y_axis);
+		ShapeInstance { x_axis: x_axis.into(), y_axis: y_axis.into(), position: transform.position.into(), scale: transform.scale, tint: tint.into() }
+	}
+}
+
+impl ShapeRenderer {
+	const INSTANCE_BUFFER_SIZE: usize = 4096;
+	const MAX_UNUSED_FRAMES: u64 = 600;
+
+	pub fn new(bits: &GameRendererBits, asset_loader: &AssetLoader) -> Result<Self> {
+		let device = &bits.device;
+
+		let vert_module = asset_loader.create_shader_module(device, "shape.vert.spv").context("failed to load shape renderer vert shader")?;
+		let frag_module = asset_loader.create_shader_module(device, "line.frag.spv").context("failed to load shape renderer frag shader")?;
+
+		let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+			label: Some("Shape pipeline layout"),
+			bind_group_layouts: &[&bits.uniform_bind_group_layout],
+			push_constant_ranges: &[],
+		});
+
+		let mesh_layout =
+			VertexBufferLayout { array_stride: std::mem::size_of::<Vert>() as BufferAddress, step_mode: InputStepMode::Vertex, attributes: &Vert::LAYOUT };
+		let instance_layout = VertexBufferLayout {
+			array_stride: std::mem::size_of::<ShapeInstance>() as BufferAddress,
+			step_mode: InputStepMode::Instance,
+			attributes: &ShapeInstance::LAYOUT,
+		};
+
+		let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+			label: Some("Shape pipeline"),
+			layout: Some(&pipeline_layout),
+			vertex: VertexState { module: &vert_module, entry_point: "main", buffers: &[mesh_layout, instance_layout] },
+			fragment: Some(FragmentState { module: &frag_module, entry_point: "main", targets: &[PostProcess::SCENE_FORMAT.into()] }),
+			primitive: PrimitiveState { cull_mode: CullMode::None, ..Default::default() },
+			depth_stencil: None,
+			multisample: Default::default(),
+		});
+
+		let size = Self::INSTANCE_BUFFER_SIZE;
+		Ok(ShapeRenderer {
+			pipeline,
+			meshes: HashMap::new(),
+			vertices: Vec::new(),
+			indices: Vec::new(),
+			meshes_dirty: false,
+			vertex_buffer: PolylineBuffer::make_buffer(device, "Shape vertex buffer", size, BufferUsage::COPY_DST | BufferUsage::VERTEX),
+			index_buffer: PolylineBuffer::make_buffer(device, "Shape index buffer", size, BufferUsage::COPY_DST | BufferUsage::INDEX),
+			instance_buffer: PolylineBuffer::make_buffer(device, "Shape instance buffer", size, BufferUsage::COPY_DST | BufferUsage::VERTEX),
+			vertex_capacity: size,
+			index_capacity: size,
+			instance_capacity: size,
+			batches: Vec::new(),
+			frame: 0,
+		})
+	}
+
+	fn add_mesh(&mut self, shape: &Shape) {
+		let key = shape.key();
+		let frame = self.frame;
+		match self.meshes.get_mut(key) {
+			Some(mesh) => mesh.last_used = frame,
+			None => {
+				let mesh = LineMesh::tessellate(shape.polyline());
+				let (first_index, first_vertex) = (self.indices.len() as u32, self.vertices.len() as u32);
+				self.vertices.extend_from_slice(&mesh.vertices);
+				self.indices.extend_from_slice(&mesh.indices);
+				self.meshes.insert(
+					key.clone(),
+					ShapeMesh { indices: first_index..self.indices.len() as u32, vertices: first_vertex..self.vertices.len() as u32, last_used: frame },
+				);
+				self.meshes_dirty = true;
+			},
+		}
+	}
+
+	/// Drops meshes unused for `MAX_UNUSED_FRAMES` and packs the rest together.
+	fn evict_unused(&mut self) {
+		let frame = self.frame;
+		let before = self.meshes.len();
+		self.meshes.retain(|_, mesh| frame - mesh.last_used <= Self::MAX_UNUSED_FRAMES);
+		if self.meshes.len() == before {
+			return;
+		}
+
+		let old_vertices = std::mem::take(&mut self.vertices);
+		let old_indices = std::mem::take(&mut self.indices);
+		for mesh in self.meshes.values_mut() {
+			let (first_index, first_vertex) = (self.indices.len() as u32, self.vertices.len() as u32);
+			self.vertices.extend_from_slice(&old_vertices[mesh.vertices.start as usize..mesh.vertices.end as usize]);
+			self.indices.extend_from_slice(&old_indices[mesh.indices.start as usize..mesh.indices.end as usize]);
+			mesh.vertices = first_vertex..self.vertices.len() as u32;
+			mesh.indices = first_index..self.indices.len() as u32;
+		}
+		self.meshes_dirty = true;
+	}
+
+	/// Shapes that haven't been drawn before get tessellated here.
+	pub fn prepare<'s>(&mut self, device: &Device, queue: &Queue, instances: impl IntoIterator<Item = (&'s Shape, ShapeInstance)>) {
+		self.frame += 1;
+
+		//all the copies of each shape next to each other, so each shape is one draw call
+		let mut by_mesh: BTreeMap<&ShapeKey, Vec<ShapeInstance>> = BTreeMap::new();
+		for (shape, instance) in instances {
+			if shape.polyline().points.len() < 2 {
+				continue;
+			}
+			self.add_mesh(shape);
+			by_mesh.entry(shape.key()).or_default().push(instance);
+		}
+		self.evict_unused();
+
+		let mut all_instances: Vec<ShapeInstance> = Vec::new();
+		self.batches.clear();
+		for (key, instances) in by_mesh {
+			let start = all_instances.len() as u32;
+			all_instances.extend(instances);
+			self.batches.push((key.clone(), start..all_instances.len() as u32));
+		}
+
+		if self.meshes_dirty && !self.indices.is_empty() {
+			self.meshes_dirty = false;
+			Self::write(
+				device,
+				queue,
+				&mut self.vertex_buffer,
+				&mut self.vertex_capacity,
+				"Shape vertex buffer",
+				BufferUsage::VERTEX,
+				bytemuck::cast_slice(&self.vertices),
+			);
+			Self::write(
+				device,
+				queue,
+				&mut self.index_buffer,
+				&mut self.index_capacity,
+				"Shape index buffer",
+				BufferUsage::INDEX,
+				bytemuck::cast_slice(&self.indices),
+			);
+		}
+		if !all_instances.is_empty() {
+			let bytes = bytemuck::cast_slice(&all_instances);
+			Self::write(device, queue, &mut self.instance_buffer, &mut self.instance_capacity, "Shape instance buffer", BufferUsage::VERTEX, bytes);
+		}
+	}
+
+	/// Grows the buffer first if `bytes` don't fit.
+	fn write(device: &Device, queue: &Queue, buffer: &mut Buffer, capacity: &mut usize, label: &str, usage: BufferUsage, bytes: &[u8]) {
+		if bytes.len() > *capacity {
+			*capacity = bytes.len().next_power_of_two();
+			*buffer = PolylineBuffer::make_buffer(device, label, *capacity, BufferUsage::COPY_DST | usage);
+		}
+		queue.write_buffer(buffer, 0, bytes);
+	}
+
+	/// Assumes bind group 0 is global uniforms
+	pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+		if self.batches.is_empty() {
+			return;
+		}
+		render_pass.set_pipeline(&self.pipeline);
+		render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+		render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+		render_pass.set_index_buffer(self.index_buffer.slice(..), IndexFormat::Uint32);
+		for (key, instances) in &self.batches {
+			let mesh = &self.meshes[key];
+			render_pass.draw_indexed(mesh.indices.clone(), mesh.vertices.start as i32, instances.clone());
+		}
+	}
+}
@@ -0,0 +1,277 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use anyhow::*;
+use ultraviolet::Rotor2;
+use ultraviolet::Vec2;
+use wgpu::*;
+
+use super::Atlas;
+use super::GameRendererBits;
+use super::PolylineBuffer;
+use super::PostProcess;
+use crate::asset_loader::AssetLoader;
+use crate::world::Transform;
+
+/// Draws textured quads, for pictures to go along with the lines. Each one is an instance with a transform, a size, a
+/// piece of its texture to show, and a tint.
+///
+/// Sprites get queued up with `draw`, then `prepare` uploads them all at once, sorted by texture so there's one draw
+/// call per texture.
+pub struct SpriteRenderer {
+	pipeline: RenderPipeline,
+	texture_layout: BindGroupLayout,
+	sampler: Sampler,
+	/// Textures get loaded the first time something draws with them. `None` if that didn't work, so it doesn't keep
+	/// trying every frame.
+	textures: HashMap<String, Option<SpriteTexture>>,
+	asset_loader: AssetLoader,
+	/// Sprites packed by the build script, which get drawn from their atlas page instead of a texture of their own.
+	pub atlas: Atlas,
+	/// Sprites drawn since the last `prepare`.
+	queued: BTreeMap<String, Vec<SpriteInstance>>,
+	instance_buffer: Buffer,
+	/// Size of the instance buffer, in bytes.
+	instance_capacity: usize,
+	/// Which texture to use for each run of instances in the instance buffer.
+	batches: Vec<(String, Range<u32>)>,
+}
+
+pub struct SpriteTexture {
+	pub width: u32,
+	pub height: u32,
+	texture: Texture,
+	bind_group: BindGroup,
+}
+
+/// One sprite.
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct SpriteInstance {
+	/// Where the sprite's width and height go in the world, rotation included.
+	x_axis: [f32; 2],
+	y_axis: [f32; 2],
+	/// The middle of the sprite.
+	position: [f32; 2],
+	uv_min: [f32; 2],
+	uv_max: [f32; 2],
+	tint: [f32; 4],
+}
+
+impl SpriteInstance {
+	#[allow(dead_code)] //no, it's used, r-a
+	pub const LAYOUT: [wgpu::VertexAttribute; 6] = wgpu::vertex_attr_array![0 => Float2, 1 => Float2, 2 => Float2, 3 => Float2, 4 => Float2, 5 => Float4];
+
+	/// A `size` sized sprite, centered on the transform's position. `uv` is left, top, right, bottom, from 0 to 1.
+	pub fn new(transform: &Transform, size: Vec2, uv: [f32; 4], tint: [f32; 4]) -> Self {
+		let rotor = Rotor2::from_angle(transform.rotation);
+		let (mut x_axis, mut y_axis) = (Vec2::new(size.x * transform.scale, 0.0), Vec2::new(0.0, size.y * transform.scale));
+		rotor.rotate_vec(&mut x_axis);
+		rotor.rotate_vec(&mut y_axis);
+		SpriteInstance {
+			x_axis: x_axis.into(),
+			y_axis: y_axis.into(),
+			position: transform.position.into(),
+			uv_min: [uv[0], uv[1]],
+			uv_max: [uv[2], uv[3]],
+			tint,
+		}
+	}
+}
+
+impl SpriteRenderer {
+	const INSTANCE_BUFFER_SIZE: usize = 4096;
+
+	pub fn new(bits: &GameRendererBits, asset_loader: &AssetLoader) -> Result<Self> {
+		let device = &bits.device;
+
+		let vert_module = asset_loader.create_shader_module(device, "sprite.vert.spv").context("failed to load sprite renderer vert shader")?;
+		let frag_module = asset_loader.create_shader_module(device, "sprite.frag.spv").context("failed to load sprite renderer frag shader")?;
+
+		let texture_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+			label: Some("Sprite texture bind layout"),
+			entries: &[
+				BindGroupLayoutEntry {
+					binding: 0,
+					visibility: ShaderStage::FRAGMENT,
+					ty: BindingType::Texture {
+						sample_type: TextureSampleType::Float { filterable: true },
+						view_dimension: TextureViewDimension::D2,
+						multisampled: false,
+					},
+					count: None,
+				},
+				BindGroupLayoutEntry {
+					binding: 1,
+					visibility: ShaderStage::FRAGMENT,
+					ty: BindingType::Sampler { filtering: true, comparison: false },
+					count: None,
+				},
+			],
+		});
+
+		//clamped, so a piece of a texture doesn't bleed into the other side of it
+		let sampler = device.create_sampler(&SamplerDescriptor {
+			label: Some("Sprite sampler"),
+			address_mode_u: AddressMode::ClampToEdge,
+			address_mode_v: AddressMode::ClampToEdge,
+			mag_filter: FilterMode::Linear,
+			min_filter: FilterMode::Linear,
+			..Default::default()
+		});
+
+		//global uniforms in group 0, same as the lines, so they can share a pass
+		let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+			label: Some("Sprite pipeline layout"),
+			bind_group_layouts: &[&bits.uniform_bind_group_layout, &texture_layout],
+			push_constant_ranges: &[],
+		});
+
+		let instance_layout = VertexBufferLayout {
+			array_stride: std::mem::size_of::<SpriteInstance>() as BufferAddress,
+			step_mode: InputStepMode::Instance,
+			attributes: &SpriteInstance::LAYOUT,
+		};
+
+		let alpha_blend = BlendState { src_factor: BlendFactor::SrcAlpha, dst_factor: BlendFactor::OneMinusSrcAlpha, operation: BlendOperation::Add };
+		let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+			label: Some("Sprite pipeline"),
+			layout: Some(&pipeline_layout),
+			vertex: VertexState { module: &vert_module, entry_point: "main", buffers: &[instance_layout] },
+			fragment: Some(FragmentState {
+				module: &frag_module,
+				entry_point: "main",
+				targets: &[ColorTargetState {
+					format: PostProcess::SCENE_FORMAT,
+					alpha_blend: alpha_blend.clone(),
+					color_blend: alpha_blend,
+					write_mask: ColorWrite::ALL,
+				}],
+			}),
+			primitive: PrimitiveState { cull_mode: CullMode::None, ..Default::default() },
+			depth_stencil: None,
+			multisample: Default::default(),
+		});
+
+		Ok(SpriteRenderer {
+			pipeline,
+			texture_layout,
+			sampler,
+			textures: HashMap::new(),
+			asset_loader: asset_loader.clone(),
+			atlas: Atlas::load(asset_loader)?,
+			queued: BTreeMap::new(),
+			instance_buffer: PolylineBuffer::make_buffer(
+				device,
+				"Sprite instance buffer",
+				Self::INSTANCE_BUFFER_SIZE,
+				BufferUsage::COPY_DST | BufferUsage::VERTEX,
+			),
+			instance_capacity: Self::INSTANCE_BUFFER_SIZE,
+			batches: Vec::new(),
+		})
+	}
+
+	/// Loads an image and uploads it to the gpu.
+	pub fn load_texture(&self, bits: &GameRendererBits, name: &str) -> Result<SpriteTexture> {
+		let image = self.asset_loader.load_image(name)?;
+		let (width, height) = image.dimensions();
+		let size = Extent3d { width, height, depth: 1 };
+
+		//srgb, so it gets sampled into the same linear colors the scene is drawn in
+		let texture = bits.device.create_texture(&TextureDescriptor {
+			label: Some(name),
+			size,
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: TextureDimension::D2,
+			format: TextureFormat::Rgba8UnormSrgb,
+			usage: TextureUsage::SAMPLED | TextureUsage::COPY_DST,
+		});
+		bits.queue.write_texture(
+			TextureCopyView { texture: &texture, mip_level: 0, origin: Origin3d::ZERO },
+			&image,
+			TextureDataLayout { offset: 0, bytes_per_row: width * 4, rows_per_image: height },
+			size,
+		);
+
+		let view = texture.create_view(&TextureViewDescriptor::default());
+		let bind_group = bits.device.create_bind_group(&BindGroupDescriptor {
+			label: Some("Sprite texture binds"),
+			layout: &self.texture_layout,
+			entries: &[
+				BindGroupEntry { binding: 0, resource: BindingResource::TextureView(&view) },
+				BindGroupEntry { binding: 1, resource: BindingResource::Sampler(&self.sampler) },
+			],
+		});
+
+		Ok(SpriteTexture { width, height, texture, bind_group })
+	}
+
+	/// Queues a sprite. `texture` is the name of a sprite in the atlas, or else the path to an image, relative to the
+	/// asset directory. Either way the instance's uv rect is within that sprite. It shows up after the next `prepare`.
+	pub fn draw(&mut self, texture: &str, mut instance: SpriteInstance) {
+		let page = match self.atlas.resolve(texture) {
+			Some((page, [left, top, right, bottom])) => {
+				let (width, height) = (right - left, bottom - top);
+				instance.uv_min = [left + instance.uv_min[0] * width, top + instance.uv_min[1] * height];
+				instance.uv_max = [left + instance.uv_max[0] * width, top + instance.uv_max[1] * height];
+				page
+			},
+			None => texture,
+		};
+		self.queued.entry(page.to_string()).or_default().push(instance);
+	}
+
+	/// Loads any textures that haven't been used before, and uploads everything that was queued.
+	pub fn prepare(&mut self, bits: &GameRendererBits) {
+		let queued = std::mem::take(&mut self.queued);
+
+		let mut all_instances: Vec<SpriteInstance> = Vec::new();
+		self.batches.clear();
+		for (name, instances) in queued {
+			if !self.textures.contains_key(&name) {
+				let texture = match self.load_texture(bits, &name) {
+					Ok(texture) => Some(texture),
+					Err(e) => {
+						log::error!("couldn't load sprite texture: {:?}", e);
+						None
+					},
+				};
+				self.textures.insert(name.clone(), texture);
+			}
+			if matches!(self.textures.get(&name), Some(Some(_))) {
+				let start = all_instances.len() as u32;
+				all_instances.extend(instances);
+				self.batches.push((name, start..all_instances.len() as u32));
+			}
+		}
+
+		if all_instances.is_empty() {
+			return;
+		}
+		let bytes: &[u8] = bytemuck::cast_slice(&all_instances);
+		if bytes.len() > self.instance_capacity {
+			self.instance_capacity = bytes.len().next_power_of_two();
+			self.instance_buffer =
+				PolylineBuffer::make_buffer(&bits.device, "Sprite instance buffer", self.instance_capacity, BufferUsage::COPY_DST | BufferUsage::VERTEX);
+		}
+		bits.queue.write_buffer(&self.instance_buffer, 0, bytes);
+	}
+
+	/// Assumes bind group 0 is global uniforms
+	pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+		if self.batches.is_empty() {
+			return;
+		}
+		render_pass.set_pipeline(&self.pipeline);
+		render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+		for (name, instances) in &self.batches {
+			if let Some(Some(texture)) = self.textures.get(name) {
+				render_pass.set_bind_group(1, &texture.bind_group, &[]);
+				render_pass.draw(0..6, instances.clone());
+			}
+		}
+	}
+}
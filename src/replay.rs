@@ -0,0 +1,217 @@
+use std::fmt::Display;
+use std::path::Path;
+
+use anyhow::*;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::asset_loader::AssetLoader;
+use crate::game::Game;
+use crate::input::Actions;
+
+pub const REPLAY_VERSION: u32 = 1;
+
+/// A recording of every tick's `Actions` from the start of a game. Playing the same actions into a game made with the
+/// same map and seed has to produce the exact same game, which the checksums are there to double-check.
+#[derive(Serialize, Deserialize)]
+pub struct Replay {
+	pub version: u32,
+	pub map: String,
+	pub seed: u64,
+	/// A checksum gets recorded every this many ticks.
+	pub checksum_interval: u64,
+	/// Run-length encoded, since most ticks look the same as the one before: (how many ticks in a row, the actions)
+	pub actions: Vec<(u32, Actions)>,
+	/// (tick, `Game::checksum` right after that tick)
+	pub checksums: Vec<(u64, u64)>,
+}
+
+impl Replay {
+	pub const DEFAULT_CHECKSUM_INTERVAL: u64 = 60;
+
+	/// Start a recording of `game`, which should be freshly created (on tick 0).
+	pub fn new(game: &Game, checksum_interval: u64) -> Self {
+		assert_eq!(game.tick, 0, "replays have to be recorded from the start of a game");
+
+		Replay {
+			version: REPLAY_VERSION,
+			map: game.map.clone(),
+			seed: game.seed,
+			checksum_interval: checksum_interval.max(1),
+			actions: Vec::new(),
+			checksums: Vec::new(),
+		}
+	}
+
+	/// Call after every `Game::update`, with the actions that were passed to it.
+	pub fn record(&mut self, actions: Actions, game: &Game) {
+		match self.actions.last_mut() {
+			Some((count, last)) if *last == actions && *count < u32::MAX => *count += 1,
+			_ => self.actions.push((1, actions)),
+		}
+
+		let last_checksum_tick = self.checksums.last().map_or(0, |(tick, _)| *tick);
+		if game.tick >= last_checksum_tick + self.checksum_interval {
+			self.checksums.push((game.tick, game.checksum()));
+		}
+	}
+
+	pub fn tick_count(&self) -> u64 {
+		self.actions.iter().map(|(count, _)| *count as u64).sum()
+	}
+
+	pub fn load(path: &Path) -> Result<Self> {
+		let contents = std::fs::read_to_string(path).with_context(|| format!("couldn't read replay {:?}", path))?;
+		let replay: Replay = ron::from_str(&contents).with_context(|| format!("couldn't parse replay {:?}", path))?;
+		if replay.version != REPLAY_VERSION {
+			bail!("replay is version {}, but this game only understands version {}", replay.version, REPLAY_VERSION);
+		}
+		Ok(replay)
+	}
+
+	pub fn save(&self, path: &Path) -> Result<()> {
+		if let Some(parent) = path.parent() {
+			std::fs::create_dir_all(parent).with_context(|| format!("couldn't create directory {:?}", parent))?;
+		}
+
+		let contents = ron::ser::to_string_pretty(self, Default::default()).context("couldn't serialize replay")?;
+		std::fs::write(path, contents).with_context(|| format!("couldn't write replay {:?}", path))
+	}
+}
+
+/// Feeds a `Replay`'s actions back out one tick at a time, and checks the game stays in sync with the recording.
+pub struct ReplayPlayer {
+	replay: Replay,
+	run: usize,
+	used_in_run: u32,
+	next_checksum: usize,
+}
+
+impl ReplayPlayer {
+	/// Makes a brand new game to play the replay into.
+	pub fn start(asset_loader: &AssetLoader, replay: Replay) -> Result<(Self, Game)> {
+		let game = Game::new(asset_loader, &replay.map, replay.seed)?;
+		Ok((ReplayPlayer { replay, run: 0, used_in_run: 0, next_checksum: 0 }, game))
+	}
+
+	/// The actions for the next tick, or None if the replay is over.
+	pub fn next_actions(&mut self) -> Option<Actions> {
+		let (count, actions) = *self.replay.actions.get(self.run)?;
+
+		self.used_in_run += 1;
+		if self.used_in_run >= count {
+			self.run += 1;
+			self.used_in_run = 0;
+		}
+
+		Some(actions)
+	}
+
+	pub fn finished(&self) -> bool {
+		self.run >= self.replay.actions.len()
+	}
+
+	/// Call after every `Game::update`. Errors if the game doesn't match what was recorded.
+	pub fn verify(&mut self, game: &Game) -> std::result::Result<(), Divergence> {
+		while let Some(&(tick, expected)) = self.replay.checksums.get(self.next_checksum) {
+			if tick > game.tick {
+				break;
+			}
+
+			self.next_checksum += 1;
+			if tick == game.tick {
+				let actual = game.checksum();
+				if actual != expected {
+					return Err(Divergence { tick, expected, actual });
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+#[derive(Debug)]
+pub struct Divergence {
+	pub tick: u64,
+	pub expected: u64,
+	pub actual: u64,
+}
+
+impl Display for Divergence {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "replay diverged on tick {}: expected checksum {:016x}, got {:016x}", self.tick, self.expected, self.actual)
+	}
+}
+
+impl std::error::Error for Divergence {}
+
+/// Plays a whole replay as fast as possible, without a window. Errors at the first sign of divergence.
+pub fn run_headless(asset_loader: &AssetLoader, replay: Replay) -> Result<Game> {
+	let (mut player, mut game) = ReplayPlayer::start(asset_loader, replay)?;
+
+	while let Some(actions) = player.next_actions() {
+		game.update(&actions);
+		game.events.clear();
+		player.verify(&game)?;
+	}
+
+	Ok(game)
+}
+
+#[cfg(test)]
+mod tests {
+	use ultraviolet::Vec2;
+
+	use super::*;
+	use crate::world::GameWorld;
+
+	fn asset_loader() -> AssetLoader {
+		AssetLoader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/asset_src"))
+	}
+
+	/// Bit of everything: thrusting, turning, poking at things.
+	fn scripted_actions(tick: u64) -> Actions {
+		Actions {
+			up: tick % 90 < 50,
+			down: tick % 200 > 180,
+			left: tick % 70 < 15,
+			right: tick % 110 < 20,
+			fire: tick % 30 < 2,
+			cursor: Vec2::new((tick * 7 % 1024) as f32, (tick * 3 % 576) as f32),
+			click: tick % 45 == 5,
+		}
+	}
+
+	fn record(ticks: u64) -> (Replay, u64) {
+		let mut game = Game::new(&asset_loader(), GameWorld::DEFAULT_MAP, 1234).unwrap();
+		let mut replay = Replay::new(&game, 10);
+		for tick in 0..ticks {
+			let actions = scripted_actions(tick);
+			game.update(&actions);
+			game.events.clear();
+			replay.record(actions, &game);
+		}
+		(replay, game.checksum())
+	}
+
+	#[test]
+	fn replays_reproduce_every_checksum() {
+		let (replay, final_checksum) = record(600);
+		assert_eq!(replay.tick_count(), 600);
+		assert_eq!(replay.checksums.len(), 60);
+
+		let game = run_headless(&asset_loader(), replay).unwrap();
+		assert_eq!(game.tick, 600);
+		assert_eq!(game.checksum(), final_checksum);
+	}
+
+	#[test]
+	fn divergence_gets_caught() {
+		let (mut replay, _) = record(100);
+		replay.checksums[5].1 ^= 1;
+		let error = run_headless(&asset_loader(), replay).err().unwrap();
+		let divergence = error.downcast_ref::<Divergence>().unwrap();
+		assert_eq!(divergence.tick, 60);
+	}
+}
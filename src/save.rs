@@ -0,0 +1,124 @@
+use std::path::PathBuf;
+
+use anyhow::*;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::game::Game;
+
+/// Bump this whenever the shape of `Game` changes in a way old saves can't be read as.
+pub const SAVE_VERSION: u32 = 1;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SaveSlot {
+	Quick,
+	Numbered(u32),
+}
+
+impl SaveSlot {
+	fn file_name(&self) -> String {
+		match self {
+			SaveSlot::Quick => "quicksave.ron".to_string(),
+			SaveSlot::Numbered(n) => format!("slot_{}.ron", n),
+		}
+	}
+}
+
+#[derive(Serialize)]
+struct SaveFileRef<'a> {
+	version: u32,
+	game: &'a Game,
+}
+
+#[derive(Deserialize)]
+struct SaveFile {
+	version: u32,
+	game: Game,
+}
+
+/// Just the version, so it can be checked before trying to parse the rest of the file.
+#[derive(Deserialize)]
+struct SaveHeader {
+	version: u32,
+}
+
+/// Writes whole `Game`s to ron files in a directory, one file per slot.
+pub struct SaveManager {
+	dir: PathBuf,
+}
+
+impl SaveManager {
+	pub fn new<T: Into<PathBuf>>(dir: T) -> Self {
+		SaveManager { dir: dir.into() }
+	}
+
+	pub fn slot_path(&self, slot: SaveSlot) -> PathBuf {
+		self.dir.join(slot.file_name())
+	}
+
+	pub fn exists(&self, slot: SaveSlot) -> bool {
+		self.slot_path(slot).exists()
+	}
+
+	pub fn save(&self, slot: SaveSlot, game: &Game) -> Result<()> {
+		std::fs::create_dir_all(&self.dir).with_context(|| format!("couldn't create save directory {:?}", self.dir))?;
+
+		let path = self.slot_path(slot);
+		std::fs::write(&path, to_string(game)?).with_context(|| format!("couldn't write save file {:?}", path))
+	}
+
+	pub fn load(&self, slot: SaveSlot) -> Result<Game> {
+		let path = self.slot_path(slot);
+		let contents = std::fs::read_to_string(&path).with_context(|| format!("couldn't read save file {:?}", path))?;
+		from_str(&contents).with_context(|| format!("couldn't load save file {:?}", path))
+	}
+}
+
+pub fn to_string(game: &Game) -> Result<String> {
+	ron::ser::to_string_pretty(&SaveFileRef { version: SAVE_VERSION, game }, Default::default()).context("couldn't serialize game")
+}
+
+pub fn from_str(contents: &str) -> Result<Game> {
+	let header: SaveHeader = ron::from_str(contents).context("couldn't read save version")?;
+	if header.version != SAVE_VERSION {
+		//when there's more than one version, upgrade old ones here instead of giving up
+		bail!("save is version {}, but this game only understands version {}", header.version, SAVE_VERSION);
+	}
+
+	let save: SaveFile = ron::from_str(contents).context("couldn't parse save")?;
+	Ok(save.game)
+}
+
+#[cfg(test)]
+mod tests {
+	use ultraviolet::Vec2;
+
+	use super::*;
+	use crate::asset_loader::AssetLoader;
+	use crate::input::Actions;
+
+	fn actions(tick: u64) -> Actions {
+		Actions { up: tick % 80 < 40, left: tick % 50 < 10, cursor: Vec2::new(300.0, 200.0), ..Default::default() }
+	}
+
+	/// Saving and loading shouldn't change the game at all, or what it does next.
+	#[test]
+	fn loaded_games_match() {
+		let asset_loader = AssetLoader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/asset_src"));
+		for map in &["world/map.ron", "world/abyss.ron", "world/orbits.ron"] {
+			let mut game = Game::new(&asset_loader, map, 99).unwrap();
+			for tick in 0..300 {
+				game.update(&actions(tick));
+			}
+
+			let mut loaded = from_str(&to_string(&game).unwrap()).unwrap();
+			assert_eq!(loaded.checksum(), game.checksum(), "{} changed when it was loaded", map);
+
+			for tick in 300..600 {
+				game.update(&actions(tick));
+				loaded.update(&actions(tick));
+				assert_eq!(loaded.checksum(), game.checksum(), "{} went differently after loading, on tick {}", map, tick + 1);
+			}
+		}
+	}
+}
@@ -0,0 +1,10 @@
+/// 64-bit FNV-1a. Not cryptographic, but unlike `DefaultHasher` it's guaranteed to give the same answer on every machine and
+/// every version of Rust, so it's fine to write to files.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+	let mut hash: u64 = 0xcbf29ce484222325;
+	for byte in bytes {
+		hash ^= *byte as u64;
+		hash = hash.wrapping_mul(0x100000001b3);
+	}
+	hash
+}
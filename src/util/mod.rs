@@ -1,3 +1,5 @@
+mod hash;
 mod serde_hell;
 
+pub use hash::*;
 pub use serde_hell::*;
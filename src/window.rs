@@ -1,44 +1,137 @@
+use std::path::Path;
+use std::time::Duration;
+use std::time::Instant;
+
 use anyhow::*;
 use winit::event::*;
 use winit::event_loop::ControlFlow;
 use winit::event_loop::EventLoop;
+use winit::window::Fullscreen;
 use winit::window::Window;
 use winit::window::WindowBuilder;
 
+use crate::asset_loader::AssetLoader;
+use crate::audio::Audio;
+use crate::debug_draw;
 use crate::game::Game;
+use crate::game::GameEvent;
+use crate::input::InputState;
+use crate::render::Capture;
 use crate::render::GameRenderer;
+use crate::replay::Replay;
+use crate::replay::ReplayPlayer;
+use crate::save::SaveManager;
+use crate::save::SaveSlot;
+
+/// If the game falls further behind than this, skip ahead instead of simulating a bunch of ticks all at once.
+const MAX_TICKS_PER_FRAME: u32 = 5;
+
+/// How the window should be set up.
+pub struct WindowSettings {
+	pub title: String,
+	pub logical_width: u32,
+	pub logical_height: u32,
+	/// Borderless, on the current monitor.
+	pub fullscreen: bool,
+	/// Waits for the monitor to refresh before showing a frame, instead of tearing.
+	pub vsync: bool,
+}
 
 pub struct GameWindow {
 	pub event_loop: EventLoop<()>,
 	pub window: Window,
+	pub vsync: bool,
 }
 
 impl GameWindow {
-	pub fn new(title: &'static str, logical_width: u32, logical_height: u32) -> Result<Self> {
+	pub fn new(settings: &WindowSettings) -> Result<Self> {
 		let event_loop = EventLoop::new();
 
 		let window = WindowBuilder::new()
 			.with_resizable(true)
-			.with_title(title)
-			.with_inner_size(winit::dpi::LogicalSize::new(logical_width, logical_height))
+			.with_title(&settings.title)
+			.with_inner_size(winit::dpi::LogicalSize::new(settings.logical_width, settings.logical_height))
+			.with_fullscreen(if settings.fullscreen { Some(Fullscreen::Borderless(None)) } else { None })
 			.build(&event_loop)
 			.expect("couldn't create window");
 
-		Ok(GameWindow { event_loop, window })
+		Ok(GameWindow { event_loop, window, vsync: settings.vsync })
 	}
 
-	pub fn run_loop(self, mut game: Game, mut renderer: GameRenderer) {
+	pub fn run_loop(self, asset_loader: AssetLoader, mut game: Game, mut renderer: GameRenderer, mut audio: Audio) {
 		let window = self.window;
+		let saves = SaveManager::new("saves");
+		let replay_path = Path::new("replays/last.ron");
+
+		let mut input = InputState::default();
+		let mut recording: Option<Replay> = None;
+		let mut playback: Option<ReplayPlayer> = None;
+
+		let tick_length = Duration::from_secs(1) / Game::TICKS_PER_SECOND;
+		let mut last_frame = Instant::now();
+		let mut unsimulated_time = Duration::default();
 
 		self.event_loop.run(move |event, _window_target, control_flow| match event {
 			Event::WindowEvent { window_id, event } if window_id == window.id() => {
-				if !game.handle_input(&event) {
+				if !input.handle_event(&event) {
 					match event {
 						WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-						WindowEvent::KeyboardInput {
-							input: KeyboardInput { state: ElementState::Pressed, virtual_keycode: Some(VirtualKeyCode::Escape), .. },
-							..
-						} => *control_flow = ControlFlow::Exit,
+						WindowEvent::KeyboardInput { input: KeyboardInput { state: ElementState::Pressed, virtual_keycode: Some(key), .. }, .. } => match key {
+							VirtualKeyCode::Escape => *control_flow = ControlFlow::Exit,
+							VirtualKeyCode::F1 => log::info!("debug drawing {}", if debug_draw::toggle() { "on" } else { "off" }),
+							VirtualKeyCode::F2 => match recording.take() {
+								//stop recording
+								Some(replay) => match replay.save(replay_path) {
+									Ok(_) => log::info!("saved replay to {:?} ({} ticks)", replay_path, replay.tick_count()),
+									Err(e) => log::error!("couldn't save replay: {:?}", e),
+								},
+								//start recording. replays have to start from the beginning, so restart the map with a new seed
+								None => match Game::new(&asset_loader, &game.map, rand::random()) {
+									Ok(fresh) => {
+										game = fresh;
+										renderer.setup(&mut game);
+										playback = None;
+										recording = Some(Replay::new(&game, Replay::DEFAULT_CHECKSUM_INTERVAL));
+										log::info!("recording replay");
+									},
+									Err(e) => log::error!("couldn't restart game for recording: {:?}", e),
+								},
+							},
+							VirtualKeyCode::F3 => match Replay::load(replay_path).and_then(|replay| ReplayPlayer::start(&asset_loader, replay)) {
+								Ok((player, fresh)) => {
+									game = fresh;
+									renderer.setup(&mut game);
+									recording = None;
+									playback = Some(player);
+									log::info!("playing replay from {:?}", replay_path);
+								},
+								Err(e) => log::error!("couldn't play replay: {:?}", e),
+							},
+							VirtualKeyCode::F5 => match saves.save(SaveSlot::Quick, &game) {
+								Ok(_) => log::info!("quicksaved"),
+								Err(e) => log::error!("couldn't quicksave: {:?}", e),
+							},
+							VirtualKeyCode::F9 => match saves.load(SaveSlot::Quick) {
+								Ok(loaded) => {
+									game = loaded;
+									renderer.setup(&mut game);
+									//the recording can't follow a jump like that
+									recording = None;
+									playback = None;
+									log::info!("quickloaded");
+								},
+								Err(e) => log::error!("couldn't quickload: {:?}", e),
+							},
+							VirtualKeyCode::F11 => {
+								if renderer.capture.is_capturing_sequence() {
+									renderer.capture.stop_sequence()
+								} else {
+									renderer.capture.start_sequence(Capture::SEQUENCE_LENGTH)
+								}
+							},
+							VirtualKeyCode::F12 => renderer.capture.screenshot(),
+							_ => (),
+						},
 						WindowEvent::Resized(physical_size) => {
 							renderer.resize(physical_size);
 						},
@@ -50,7 +143,55 @@ impl GameWindow {
 				}
 			},
 			Event::MainEventsCleared => {
-				game.update();
+				//fixed timestep, so the game runs the same no matter the framerate
+				let now = Instant::now();
+				unsimulated_time += now - last_frame;
+				last_frame = now;
+
+				let mut ticks = 0;
+				while unsimulated_time >= tick_length {
+					unsimulated_time -= tick_length;
+					ticks += 1;
+					if ticks > MAX_TICKS_PER_FRAME {
+						//way behind, probably sat on a breakpoint or something. just give up on catching up
+						unsimulated_time = Duration::default();
+						break;
+					}
+
+					let mut live_actions = input.take_actions();
+					live_actions.cursor = renderer.screen_to_world(live_actions.cursor);
+					let actions = match playback.as_mut().and_then(|p| p.next_actions()) {
+						Some(replayed) => replayed,
+						None => live_actions,
+					};
+
+					game.update(&actions);
+
+					if let Some(player) = playback.as_mut() {
+						if let Err(divergence) = player.verify(&game) {
+							log::error!("{}", divergence);
+							playback = None;
+						} else if player.finished() {
+							log::info!("replay finished");
+							playback = None;
+						}
+					}
+
+					if let Some(replay) = recording.as_mut() {
+						replay.record(actions, &game);
+					}
+				}
+
+				//listen from wherever the camera is looking
+				audio.listener.position = renderer.camera.position;
+				for event in game.events.drain(..) {
+					if let GameEvent::Message(text) = &event {
+						//no text rendering yet
+						log::info!("message: {}", text);
+					}
+					audio.handle_event(&event);
+					renderer.camera.handle_event(&event);
+				}
 
 				match renderer.render(&mut game) {
 					Ok(_) => (),
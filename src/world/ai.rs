@@ -0,0 +1,227 @@
+//! Enemy brains: a little state machine on top of steering behaviours.
+
+use std::collections::HashMap;
+
+use anyhow::*;
+use rand_pcg::Pcg32;
+use serde::Deserialize;
+use serde::Serialize;
+use ultraviolet::Vec2;
+
+use crate::asset_loader::AssetLoader;
+use crate::debug_draw;
+use crate::debug_draw::DebugDraw;
+use crate::game::GameEvent;
+use crate::world::steering;
+use crate::world::EntityId;
+use crate::world::GameWorld;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EnemyParams {
+	pub max_speed: f32,
+	pub max_force: f32,
+	/// For avoiding walls and pathfinding.
+	pub radius: f32,
+	/// Fraction of `max_speed`, while patrolling or heading home.
+	pub patrol_speed: f32,
+	pub slow_radius: f32,
+
+	pub wander_radius: f32,
+	pub wander_distance: f32,
+	/// Radians per tick.
+	pub wander_jitter: f32,
+	pub look_ahead: f32,
+	pub avoid_weight: f32,
+
+	pub sight_range: f32,
+	pub attack_range: f32,
+	pub lunge_speed: f32,
+	/// Ticks.
+	pub attack_cooldown: u32,
+	/// Ticks it keeps chasing after losing sight of the player.
+	pub memory: u32,
+	pub leash: f32,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EnemyTypes(pub HashMap<String, EnemyParams>);
+
+impl EnemyTypes {
+	pub const FILE: &'static str = "world/enemies.ron";
+
+	pub fn load(asset_loader: &AssetLoader) -> Result<Self> {
+		let file = asset_loader.load_string(Self::FILE)?;
+		ron::from_str(&file).with_context(|| anyhow!("couldnt parse enemy types '{}'", Self::FILE))
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AiState {
+	/// Wanders near home if it hasn't got a patrol route.
+	Patrol,
+	Chase {
+		lost_for: u32,
+	},
+	Attack {
+		cooldown: u32,
+	},
+	Return,
+}
+
+/// How a brain is written in a map file.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BrainDef {
+	pub kind: String,
+	#[serde(default, with = "crate::util::vec_of_vec2")]
+	pub patrol: Vec<Vec2>,
+}
+
+/// An enemy's thoughts. Carries its own copy of the params, so saves don't depend on the enemy file.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Brain {
+	pub params: EnemyParams,
+	pub state: AiState,
+	#[serde(with = "crate::util::NotVec2")]
+	pub home: Vec2,
+	#[serde(with = "crate::util::vec_of_vec2")]
+	pub patrol: Vec<Vec2>,
+	pub patrol_index: usize,
+	pub wander_angle: f32,
+	#[serde(with = "crate::util::NotVec2")]
+	pub last_seen: Vec2,
+	#[serde(with = "crate::util::vec_of_vec2")]
+	pub path: Vec<Vec2>,
+}
+
+impl Brain {
+	pub fn new(params: EnemyParams, home: Vec2, patrol: Vec<Vec2>) -> Self {
+		Brain { params, state: AiState::Patrol, home, patrol, patrol_index: 0, wander_angle: 0.0, last_seen: home, path: Vec::new() }
+	}
+}
+
+const WAYPOINT_RADIUS: f32 = 16.0;
+
+pub fn think(world: &mut GameWorld, rng: &mut Pcg32, dt: f32, events: &mut Vec<GameEvent>, debug: &mut DebugDraw) {
+	let thinkers: Vec<EntityId> = world.entities.brains.iter().map(|(id, _)| id).collect();
+	let players: Vec<Vec2> = world.entities.with_tag("player").filter_map(|id| world.entities.transforms.get(id).map(|t| t.position)).collect();
+
+	for id in thinkers {
+		let (position, velocity) = match (world.entities.transforms.get(id), world.entities.velocities.get(id)) {
+			(Some(transform), Some(velocity)) => (transform.position, velocity.linear),
+			_ => continue,
+		};
+		let mut brain = match world.entities.brains.get(id) {
+			Some(brain) => brain.clone(),
+			None => continue,
+		};
+		let p = brain.params.clone();
+
+		//perception: the closest player it can actually see
+		let target = players
+			.iter()
+			.copied()
+			.filter(|player| (*player - position).mag() <= p.sight_range && world.lines.line_of_sight(position, *player))
+			.min_by(|a, b| (*a - position).mag_sq().total_cmp(&(*b - position).mag_sq()));
+		if let Some(target) = target {
+			brain.last_seen = target;
+		}
+		let too_far_from_home = (position - brain.home).mag() > p.leash;
+
+		brain.state = match brain.state.clone() {
+			AiState::Patrol if target.is_some() => AiState::Chase { lost_for: 0 },
+			AiState::Patrol => AiState::Patrol,
+			AiState::Chase { .. } if too_far_from_home => AiState::Return,
+			AiState::Chase { .. } if matches!(target, Some(t) if (t - position).mag() <= p.attack_range) => {
+				events.push(GameEvent::Named { name: "lunge".into(), position });
+				AiState::Attack { cooldown: p.attack_cooldown }
+			},
+			AiState::Chase { .. } if target.is_some() => AiState::Chase { lost_for: 0 },
+			AiState::Chase { lost_for } if lost_for >= p.memory => AiState::Return,
+			AiState::Chase { lost_for } => AiState::Chase { lost_for: lost_for + 1 },
+			AiState::Attack { cooldown: 0 } => AiState::Chase { lost_for: 0 },
+			AiState::Attack { cooldown } => AiState::Attack { cooldown: cooldown - 1 },
+			AiState::Return if (position - brain.home).mag() <= WAYPOINT_RADIUS => AiState::Patrol,
+			AiState::Return if target.is_some() && !too_far_from_home => AiState::Chase { lost_for: 0 },
+			AiState::Return => AiState::Return,
+		};
+
+		if brain.state == AiState::Return && brain.path.is_empty() {
+			brain.path = world.find_path(position, brain.home, p.radius).map(|path| path.into_iter().skip(1).collect()).unwrap_or_else(|| vec![brain.home]);
+		} else if brain.state != AiState::Return {
+			brain.path.clear();
+		}
+
+		let cruise = p.max_speed * p.patrol_speed;
+		let mut force = match &brain.state {
+			AiState::Patrol if brain.patrol.is_empty() => {
+				//wander, but don't stray too far
+				let wandering = steering::wander(velocity, &mut brain.wander_angle, rng, p.wander_radius, p.wander_distance, p.wander_jitter, cruise);
+				let homing = if (position - brain.home).mag() > p.leash / 2.0 { steering::seek(position, velocity, brain.home, cruise) } else { Vec2::zero() };
+				wandering + homing
+			},
+			AiState::Patrol => {
+				if (brain.patrol[brain.patrol_index % brain.patrol.len()] - position).mag() <= WAYPOINT_RADIUS {
+					brain.patrol_index = (brain.patrol_index + 1) % brain.patrol.len();
+				}
+				steering::arrive(position, velocity, brain.patrol[brain.patrol_index % brain.patrol.len()], cruise, p.slow_radius)
+			},
+			//heads for wherever it last saw the player, if it can't see them now
+			AiState::Chase { .. } => match target {
+				Some(target) => steering::seek(position, velocity, target, p.max_speed),
+				None => steering::arrive(position, velocity, brain.last_seen, p.max_speed, p.slow_radius),
+			},
+			AiState::Attack { cooldown } if *cooldown == p.attack_cooldown => match target {
+				//the lunge itself: straight at them, all at once
+				Some(target) => steering::seek(position, velocity, target, p.lunge_speed) / dt,
+				None => Vec2::zero(),
+			},
+			AiState::Attack { .. } => match target {
+				Some(target) => steering::flee(position, velocity, target, cruise),
+				None => -velocity,
+			},
+			AiState::Return => {
+				if brain.path.len() > 1 && (brain.path[0] - position).mag() <= WAYPOINT_RADIUS {
+					brain.path.remove(0);
+				}
+				let waypoint = brain.path.first().copied().unwrap_or(brain.home);
+				steering::arrive(position, velocity, waypoint, cruise, p.slow_radius)
+			},
+		};
+
+		let lunging = matches!(brain.state, AiState::Attack { cooldown } if cooldown == p.attack_cooldown);
+		if !lunging {
+			force = steering::truncate(force, p.max_force);
+		}
+		force += steering::avoid_obstacles(&world.lines, position, velocity, p.radius, p.look_ahead, p.max_force) * p.avoid_weight;
+
+		//checked up front, so the label and path don't get built for nothing
+		if debug_draw::is_enabled() {
+			debug.circle(position, p.sight_range, DebugDraw::BLUE);
+			debug.text(position + Vec2::new(-p.radius, -p.radius - 16.0), &format!("{:?}", brain.state), 8.0, DebugDraw::WHITE);
+			if !brain.path.is_empty() {
+				debug.polyline(std::iter::once(position).chain(brain.path.iter().copied()).collect(), DebugDraw::GREEN);
+			}
+			if let Some(target) = target {
+				debug.line(position, target, DebugDraw::RED);
+			}
+			debug.arrow(position, position + force * 0.1, DebugDraw::YELLOW);
+		}
+
+		if let Some(velocity) = world.entities.velocities.get_mut(id) {
+			velocity.linear += force * dt;
+			if !lunging {
+				velocity.linear = steering::truncate(velocity.linear, p.max_speed);
+			}
+			velocity.angular = 0.0;
+
+			//face where it's going
+			if let Some(transform) = world.entities.transforms.get_mut(id) {
+				if velocity.linear.mag_sq() > 1.0 {
+					transform.rotation = velocity.linear.x.atan2(-velocity.linear.y);
+				}
+			}
+		}
+
+		world.entities.brains.insert(id, brain);
+	}
+}
@@ -0,0 +1,291 @@
+use std::ops::Range;
+
+use anyhow::*;
+use noise::NoiseFn;
+use noise::OpenSimplex;
+use noise::Seedable;
+use rand::Rng;
+use rand_pcg::Pcg32;
+use serde::Deserialize;
+use serde::Serialize;
+use ultraviolet::Vec2;
+use ultraviolet::Vec3;
+
+use crate::asset_loader::AssetLoader;
+use crate::world::Polyline;
+
+/// Distances are in world units, y goes down.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CaveParams {
+	pub start_x: f32,
+	/// Only used when generating a whole cave.
+	pub depth: f32,
+	pub step: f32,
+
+	pub wander_amplitude: f32,
+	pub wander_frequency: f32,
+	pub base_width: f32,
+	pub width_variation: f32,
+	pub width_frequency: f32,
+	pub min_width: f32,
+
+	/// A side tunnel might branch off every this many units of depth.
+	pub branch_spacing: f32,
+	pub branch_chance: f32,
+	pub branch_length: (f32, f32),
+	pub branch_width: (f32, f32),
+	/// (min, max) radians below horizontal.
+	pub branch_angle: (f32, f32),
+	/// Radians.
+	pub branch_wiggle: f32,
+
+	#[serde(with = "crate::util::NotVec3")]
+	pub wall_color: Vec3,
+	pub wall_thickness: f32,
+}
+
+impl CaveParams {
+	pub fn load(asset_loader: &AssetLoader, name: &str) -> Result<Self> {
+		let file = asset_loader.load_string(name)?;
+		ron::from_str(&file).with_context(|| anyhow!("couldnt parse cave params '{}'", name))
+	}
+}
+
+struct Branch {
+	y: f32,
+	side: f32,
+	length: f32,
+	width: f32,
+	angle: f32,
+	seed: u64,
+}
+
+/// Endless winding cave walls. Everything is a pure function of seed and depth, so slices line up with their neighbours.
+pub struct CaveGenerator {
+	pub params: CaveParams,
+	pub seed: u64,
+	wander: OpenSimplex,
+	width: OpenSimplex,
+}
+
+impl CaveGenerator {
+	pub fn new(params: CaveParams, seed: u64) -> Self {
+		let wander = OpenSimplex::new().set_seed(seed as u32);
+		let width = OpenSimplex::new().set_seed((seed as u32).wrapping_add(1));
+		CaveGenerator { params, seed, wander, width }
+	}
+
+	/// Down to `params.depth`, with a floor.
+	pub fn generate(&self) -> Vec<Polyline> {
+		let bottom = self.snap(self.params.depth);
+		let mut lines = self.generate_span(0.0..bottom);
+
+		let floor = vec![self.left_wall(bottom), self.right_wall(bottom)];
+		lines.push(self.wall(floor));
+		lines
+	}
+
+	/// `depths` gets snapped to multiples of `params.step`, so neighbouring spans share their end points exactly. Side
+	/// tunnels belong to the span they start in.
+	pub fn generate_span(&self, depths: Range<f32>) -> Vec<Polyline> {
+		let step = self.params.step;
+		let first = (depths.start / step).round() as i64;
+		let last = (depths.end / step).round() as i64;
+		if last <= first {
+			return Vec::new();
+		}
+
+		//side tunnels from just outside this span might still need a gap in the walls in here
+		let max_mouth = self.params.branch_width.1 / self.params.branch_angle.1.cos().max(0.2);
+		let nearby_branches = self.branches_in(depths.start - max_mouth..depths.end + max_mouth);
+		let mut lines = Vec::new();
+
+		for &side in &[-1.0, 1.0] {
+			//walk down the wall, leaving gaps where side tunnels come out
+			let mut current: Vec<Vec2> = Vec::new();
+			for i in first..=last {
+				let y = i as f32 * step;
+				let gap = nearby_branches.iter().find(|b| b.side == side && (y - b.y).abs() < self.mouth_height(b) / 2.0);
+
+				if gap.is_some() {
+					if current.len() >= 2 {
+						lines.push(self.wall(std::mem::take(&mut current)));
+					}
+					current.clear();
+				} else {
+					current.push(self.wall_point(y, side));
+				}
+			}
+
+			if current.len() >= 2 {
+				lines.push(self.wall(current));
+			}
+		}
+
+		for branch in self.branches_in(depths) {
+			lines.push(self.branch_wall(&branch));
+		}
+
+		lines
+	}
+
+	pub fn center(&self, y: f32) -> f32 {
+		let p = &self.params;
+		p.start_x + self.wander.get([(y * p.wander_frequency) as f64, 0.0]) as f32 * p.wander_amplitude
+	}
+
+	pub fn width(&self, y: f32) -> f32 {
+		let p = &self.params;
+		(p.base_width + self.width.get([(y * p.width_frequency) as f64, 0.0]) as f32 * p.width_variation).max(p.min_width)
+	}
+
+	pub fn left_wall(&self, y: f32) -> Vec2 {
+		self.wall_point(y, -1.0)
+	}
+
+	pub fn right_wall(&self, y: f32) -> Vec2 {
+		self.wall_point(y, 1.0)
+	}
+
+	fn wall_point(&self, y: f32, side: f32) -> Vec2 {
+		Vec2::new(self.center(y) + side * self.width(y) / 2.0, y)
+	}
+
+	fn snap(&self, y: f32) -> f32 {
+		(y / self.params.step).round() * self.params.step
+	}
+
+	fn wall(&self, points: Vec<Vec2>) -> Polyline {
+		Polyline::new(points, self.params.wall_color, self.params.wall_thickness)
+	}
+
+	/// Each slot gets its own rng, so generation order doesn't matter.
+	fn branches_in(&self, depths: Range<f32>) -> Vec<Branch> {
+		let p = &self.params;
+		let first_slot = (depths.start / p.branch_spacing).ceil().max(1.0) as u64;
+		let last_slot = (depths.end / p.branch_spacing).ceil() as u64;
+
+		(first_slot..last_slot)
+			.filter_map(|slot| {
+				let mut rng = Pcg32::new(self.seed, slot);
+				if rng.gen::<f32>() >= p.branch_chance {
+					return None;
+				}
+
+				Some(Branch {
+					y: self.snap(slot as f32 * p.branch_spacing),
+					side: if rng.gen() { 1.0 } else { -1.0 },
+					length: rng.gen_range(p.branch_length.0..=p.branch_length.1),
+					width: rng.gen_range(p.branch_width.0..=p.branch_width.1),
+					angle: rng.gen_range(p.branch_angle.0..=p.branch_angle.1),
+					seed: rng.gen(),
+				})
+			})
+			.collect()
+	}
+
+	fn mouth_height(&self, branch: &Branch) -> f32 {
+		branch.width / branch.angle.cos().max(0.2)
+	}
+
+	/// Out along the top, around the end, back along the bottom.
+	fn branch_wall(&self, branch: &Branch) -> Polyline {
+		let p = &self.params;
+		let mut rng = Pcg32::new(branch.seed, 0);
+
+		//start from the last points on the main wall on either side of the gap, so there's no seam
+		let half_mouth = self.mouth_height(branch) / 2.0;
+		let top_start = self.wall_point(((branch.y - half_mouth) / p.step).floor() * p.step, branch.side);
+		let bottom_start = self.wall_point(((branch.y + half_mouth) / p.step).ceil() * p.step, branch.side);
+
+		let mut center = (top_start + bottom_start) / 2.0;
+		let mut angle = branch.angle;
+		let segments = (branch.length / p.step).ceil().max(1.0) as usize;
+
+		let mut top = vec![top_start];
+		let mut bottom = vec![bottom_start];
+		for _ in 0..segments {
+			angle = (angle + rng.gen_range(-p.branch_wiggle..=p.branch_wiggle)).clamp(0.0, std::f32::consts::FRAC_PI_2 * 0.9);
+
+			//heading outwards (in the direction of `side`) and downwards
+			let direction = Vec2::new(angle.cos() * branch.side, angle.sin());
+			let normal = Vec2::new(direction.y, -direction.x) * branch.side;
+			center += direction * p.step;
+
+			top.push(center + normal * branch.width / 2.0);
+			bottom.push(center - normal * branch.width / 2.0);
+		}
+
+		let tip = center + Vec2::new(angle.cos() * branch.side, angle.sin()) * branch.width / 2.0;
+		top.push(tip);
+		top.extend(bottom.into_iter().rev());
+		self.wall(top)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn params(branch_chance: f32) -> CaveParams {
+		CaveParams {
+			start_x: 512.0,
+			depth: 4000.0,
+			step: 24.0,
+			wander_amplitude: 350.0,
+			wander_frequency: 0.0012,
+			base_width: 280.0,
+			width_variation: 120.0,
+			width_frequency: 0.004,
+			min_width: 140.0,
+			branch_spacing: 450.0,
+			branch_chance,
+			branch_length: (200.0, 700.0),
+			branch_width: (80.0, 140.0),
+			branch_angle: (0.2, 0.9),
+			branch_wiggle: 0.15,
+			wall_color: Vec3::one(),
+			wall_thickness: 10.0,
+		}
+	}
+
+	fn points(lines: &[Polyline]) -> Vec<Vec<Vec2>> {
+		lines.iter().map(|line| line.points.clone()).collect()
+	}
+
+	#[test]
+	fn same_seed_same_cave() {
+		let a = CaveGenerator::new(params(0.5), 1234).generate();
+		let b = CaveGenerator::new(params(0.5), 1234).generate();
+		assert!(!a.is_empty());
+		assert_eq!(points(&a), points(&b));
+
+		let other = CaveGenerator::new(params(0.5), 4321).generate();
+		assert_ne!(points(&a), points(&other));
+	}
+
+	#[test]
+	fn spans_meet_at_the_seams() {
+		let generator = CaveGenerator::new(params(0.0), 99);
+		let above = generator.generate_span(0.0..1200.0);
+		let below = generator.generate_span(1200.0..2400.0);
+
+		//no side tunnels, so it's just a left and a right wall in each
+		assert_eq!(above.len(), 2);
+		assert_eq!(below.len(), 2);
+		for (upper, lower) in above.iter().zip(&below) {
+			assert_eq!(upper.points.last(), lower.points.first());
+		}
+	}
+
+	#[test]
+	fn spans_add_up_to_the_whole() {
+		let generator = CaveGenerator::new(params(0.5), 7);
+		let whole: Vec<Vec2> = generator.generate_span(0.0..2400.0).into_iter().flat_map(|line| line.points).collect();
+		let pieces: Vec<Vec2> =
+			generator.generate_span(0.0..1200.0).into_iter().chain(generator.generate_span(1200.0..2400.0)).flat_map(|line| line.points).collect();
+
+		assert!(whole.iter().all(|point| pieces.contains(point)));
+		assert!(pieces.iter().all(|point| whole.contains(point)));
+	}
+}
@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::world::cave_gen::CaveGenerator;
+use crate::world::cave_gen::CaveParams;
+use crate::world::destruction::cut_polyline;
+use crate::world::destruction::Cut;
+use crate::world::LineId;
+use crate::world::Lines;
+
+/// How an endless map is written in a map file.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StreamingDef {
+	/// Path to the `CaveParams` file the chunks get generated from.
+	pub cave: String,
+	/// How tall each chunk is, in world units.
+	pub chunk_height: f32,
+	/// How many chunks to keep generated below the chunk the focus is in.
+	pub ahead: u32,
+	/// How many chunks to keep above it, before they get dropped.
+	pub behind: u32,
+}
+
+/// One horizontal slice of the world, and the lines that were generated for it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Chunk {
+	/// Chunk `i` covers depths `i * chunk_height .. (i + 1) * chunk_height`.
+	pub index: i64,
+	pub lines: Vec<LineId>,
+}
+
+/// Keeps the slice of an endless cave around the focus generated, and drops the chunks far away from it, so memory use
+/// doesn't depend on how deep the player goes. Chunks come out the same every time they're generated, with any cuts
+/// made into them made again, so dropping one and coming back to it later is seamless.
+#[derive(Serialize, Deserialize)]
+pub struct ChunkStreamer {
+	pub def: StreamingDef,
+	pub params: CaveParams,
+	pub seed: u64,
+	/// Sorted by index.
+	pub chunks: Vec<Chunk>,
+	/// Every cut that's been made into each chunk, in order, so they can be made again when it gets generated again.
+	#[serde(default)]
+	pub cuts: BTreeMap<i64, Vec<Cut>>,
+	#[serde(skip)]
+	generator: Option<CaveGenerator>,
+}
+
+impl ChunkStreamer {
+	pub fn new(def: StreamingDef, params: CaveParams, seed: u64) -> Self {
+		ChunkStreamer { def, params, seed, chunks: Vec::new(), cuts: BTreeMap::new(), generator: None }
+	}
+
+	/// Which chunk indices should be loaded with the focus at this depth.
+	pub fn wanted(&self, focus_y: f32) -> RangeInclusive<i64> {
+		let current = (focus_y / self.def.chunk_height).floor() as i64;
+		(current - self.def.behind as i64).max(0)..=(current + self.def.ahead as i64).max(0)
+	}
+
+	/// Generates missing chunks around `focus_y` and drops ones that are too far away, adding and removing their lines
+	/// from `lines`. Returns whether anything changed.
+	pub fn update(&mut self, focus_y: f32, lines: &mut Lines) -> bool {
+		let wanted = self.wanted(focus_y);
+		let before = self.chunks.len();
+
+		self.chunks.retain(|chunk| {
+			if wanted.contains(&chunk.index) {
+				return true;
+			}
+			for &id in &chunk.lines {
+				lines.remove(id);
+			}
+			false
+		});
+		let mut changed = self.chunks.len() != before;
+
+		let (params, seed) = (&self.params, self.seed);
+		let generator = self.generator.get_or_insert_with(|| CaveGenerator::new(params.clone(), seed));
+
+		for index in wanted {
+			if self.chunks.iter().any(|chunk| chunk.index == index) {
+				continue;
+			}
+
+			let top = index as f32 * self.def.chunk_height;
+			let mut generated = generator.generate_span(top..top + self.def.chunk_height);
+			for cut in self.cuts.get(&index).into_iter().flatten() {
+				generated = generated.into_iter().flat_map(|line| cut_polyline(&line, cut).unwrap_or_else(|| vec![line])).collect();
+			}
+			let ids = generated.into_iter().map(|line| lines.insert(line)).collect();
+			self.chunks.push(Chunk { index, lines: ids });
+			changed = true;
+		}
+
+		self.chunks.sort_by_key(|chunk| chunk.index);
+		changed
+	}
+
+	pub fn chunk(&self, index: i64) -> Option<&Chunk> {
+		self.chunks.iter().find(|chunk| chunk.index == index)
+	}
+
+	/// Which chunk this line belongs to. `None` means it's not one of the streamed ones.
+	pub fn chunk_of(&self, id: LineId) -> Option<i64> {
+		self.chunks.iter().find(|chunk| chunk.lines.contains(&id)).map(|chunk| chunk.index)
+	}
+}
@@ -0,0 +1,41 @@
+use serde::Deserialize;
+use serde::Serialize;
+use ultraviolet::Vec2;
+
+use crate::world::Transform;
+
+/// A picture stuck onto the world, like a background or a bit of grime on a wall. Drawn under the lines, and doesn't
+/// touch anything.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Decal {
+	/// Name of a sprite in the atlas, or else the path to an image, relative to the asset directory.
+	pub texture: String,
+	/// Where the middle of it goes.
+	#[serde(with = "crate::util::NotVec2")]
+	pub position: Vec2,
+	/// Width and height, in world units.
+	#[serde(with = "crate::util::NotVec2")]
+	pub size: Vec2,
+	#[serde(default)]
+	pub rotation: f32,
+	/// Which part of the sprite to show: left, top, right, bottom, from 0 to 1.
+	#[serde(default = "whole_image")]
+	pub uv: [f32; 4],
+	/// Multiplied with the image. Red, green, blue, alpha.
+	#[serde(default = "white")]
+	pub tint: [f32; 4],
+}
+
+impl Decal {
+	pub fn transform(&self) -> Transform {
+		Transform { position: self.position, rotation: self.rotation, scale: 1.0 }
+	}
+}
+
+fn whole_image() -> [f32; 4] {
+	[0.0, 0.0, 1.0, 1.0]
+}
+
+fn white() -> [f32; 4] {
+	[1.0; 4]
+}
@@ -0,0 +1,193 @@
+//! Blowing holes in world lines. Cuts into streamed chunks are remembered by the `ChunkStreamer`.
+
+use std::collections::BTreeSet;
+
+use serde::Deserialize;
+use serde::Serialize;
+use ultraviolet::Lerp;
+use ultraviolet::Vec2;
+
+use crate::world::polygon_contains;
+use crate::world::GameWorld;
+use crate::world::LineId;
+use crate::world::Polyline;
+
+/// The area to remove.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Cut {
+	Circle {
+		#[serde(with = "crate::util::NotVec2")]
+		center: Vec2,
+		radius: f32,
+	},
+	Polygon(#[serde(with = "crate::util::vec_of_vec2")] Vec<Vec2>),
+}
+
+/// Pieces shorter than this are dropped instead of being kept as specks.
+const MIN_PIECE_LENGTH: f32 = 0.5;
+
+impl Cut {
+	pub fn contains(&self, point: Vec2) -> bool {
+		match self {
+			Cut::Circle { center, radius } => (point - *center).mag_sq() < radius * radius,
+			Cut::Polygon(points) => polygon_contains(points, point),
+		}
+	}
+
+	/// A circle around the whole cut, for finding lines that might be affected.
+	fn bounding_circle(&self) -> (Vec2, f32) {
+		match self {
+			Cut::Circle { center, radius } => (*center, *radius),
+			Cut::Polygon(points) => {
+				let center = points.iter().fold(Vec2::zero(), |sum, p| sum + *p) / points.len().max(1) as f32;
+				let radius = points.iter().map(|p| (*p - center).mag()).fold(0.0, f32::max);
+				(center, radius)
+			},
+		}
+	}
+
+	/// Where the segment from `a` to `b` crosses the edge of the cut, as fractions of the way along it.
+	fn crossings(&self, a: Vec2, b: Vec2) -> Vec<f32> {
+		let ba = b - a;
+		match self {
+			Cut::Circle { center, radius } => {
+				let to_a = a - *center;
+				let (qa, qb, qc) = (ba.dot(ba), 2.0 * to_a.dot(ba), to_a.dot(to_a) - radius * radius);
+				let discriminant = qb * qb - 4.0 * qa * qc;
+				if qa <= f32::EPSILON || discriminant < 0.0 {
+					return Vec::new();
+				}
+				let root = discriminant.sqrt();
+				vec![(-qb - root) / (2.0 * qa), (-qb + root) / (2.0 * qa)]
+			},
+			Cut::Polygon(points) => {
+				let mut crossings = Vec::new();
+				let mut j = points.len().wrapping_sub(1);
+				for i in 0..points.len() {
+					let (c, d) = (points[j], points[i]);
+					let dc = d - c;
+					let denominator = ba.x * dc.y - ba.y * dc.x;
+					if denominator.abs() > f32::EPSILON {
+						let ca = c - a;
+						let t = (ca.x * dc.y - ca.y * dc.x) / denominator;
+						let u = (ca.x * ba.y - ca.y * ba.x) / denominator;
+						if (0.0..=1.0).contains(&u) {
+							crossings.push(t);
+						}
+					}
+					j = i;
+				}
+				crossings
+			},
+		}
+	}
+
+	/// The parts of the segment from `a` to `b` that are outside the cut, as (from, to) fractions of the way along it.
+	fn outside_spans(&self, a: Vec2, b: Vec2) -> Vec<(f32, f32)> {
+		let mut stops = vec![0.0];
+		stops.extend(self.crossings(a, b).into_iter().filter(|t| *t > 0.0 && *t < 1.0));
+		stops.push(1.0);
+		stops.sort_by(|x, y| x.partial_cmp(y).unwrap());
+
+		let mut spans: Vec<(f32, f32)> = Vec::new();
+		for &[from, to] in stops.array_windows() {
+			if to <= from || self.contains(a.lerp(b, (from + to) / 2.0)) {
+				continue;
+			}
+			match spans.last_mut() {
+				Some(last) if last.1 == from => last.1 = to,
+				_ => spans.push((from, to)),
+			}
+		}
+		spans
+	}
+}
+
+/// What's left of `line` after taking out the parts inside `cut`, or `None` if the cut doesn't touch it.
+pub fn cut_polyline(line: &Polyline, cut: &Cut) -> Option<Vec<Polyline>> {
+	let mut pieces: Vec<Vec<Vec2>> = Vec::new();
+	let mut current: Vec<Vec2> = Vec::new();
+	let mut touched = false;
+
+	for &[a, b] in line.points.array_windows() {
+		let spans = cut.outside_spans(a, b);
+		if spans.len() != 1 || spans[0] != (0.0, 1.0) {
+			touched = true;
+		}
+		if spans.is_empty() {
+			finish_piece(&mut current, &mut pieces);
+		}
+
+		for (from, to) in spans {
+			if from > 0.0 {
+				finish_piece(&mut current, &mut pieces);
+			}
+			if current.is_empty() {
+				current.push(a.lerp(b, from));
+			}
+			current.push(a.lerp(b, to));
+			if to < 1.0 {
+				finish_piece(&mut current, &mut pieces);
+			}
+		}
+	}
+	finish_piece(&mut current, &mut pieces);
+
+	if !touched {
+		return None;
+	}
+
+	//a loop that got cut open should be one piece going around, not two that meet where the loop started
+	if line.is_closed() && pieces.len() >= 2 && pieces[0][0] == line.points[0] && pieces[pieces.len() - 1].last() == line.points.last() {
+		let first = pieces.remove(0);
+		pieces.last_mut().unwrap().extend_from_slice(&first[1..]);
+	}
+
+	Some(pieces.into_iter().map(|points| Polyline::new(points, line.color, line.thickness)).filter(|piece| piece.length() >= MIN_PIECE_LENGTH).collect())
+}
+
+fn finish_piece(current: &mut Vec<Vec2>, pieces: &mut Vec<Vec<Vec2>>) {
+	if current.len() >= 2 {
+		pieces.push(std::mem::take(current));
+	}
+	current.clear();
+}
+
+impl GameWorld {
+	/// Removes the parts of every world line inside `cut`, replacing cut lines with what's left of them. Returns the
+	/// ids of the new pieces.
+	pub fn cut(&mut self, cut: &Cut) -> Vec<LineId> {
+		let (center, radius) = cut.bounding_circle();
+		let mut new_pieces = Vec::new();
+		let mut cut_chunks = BTreeSet::new();
+
+		for id in self.lines.lines_near(center, radius) {
+			let pieces = match self.lines.get(id).and_then(|line| cut_polyline(line, cut)) {
+				Some(pieces) => pieces,
+				None => continue,
+			};
+
+			self.lines.remove(id);
+			let ids: Vec<LineId> = pieces.into_iter().map(|piece| self.lines.insert(piece)).collect();
+
+			//pieces of a chunk's line belong to that chunk too, so they get dropped with it
+			if let Some(streamer) = &mut self.streaming {
+				if let Some(chunk) = streamer.chunks.iter_mut().find(|chunk| chunk.lines.contains(&id)) {
+					chunk.lines.retain(|line| *line != id);
+					chunk.lines.extend_from_slice(&ids);
+					cut_chunks.insert(chunk.index);
+				}
+			}
+
+			new_pieces.extend(ids);
+		}
+
+		if let Some(streamer) = &mut self.streaming {
+			for index in cut_chunks {
+				streamer.cuts.entry(index).or_default().push(cut.clone());
+			}
+		}
+
+		new_pieces
+	}
+}
@@ -0,0 +1,307 @@
+use serde::Deserialize;
+use serde::Serialize;
+use ultraviolet::Rotor2;
+use ultraviolet::Vec2;
+
+use crate::world::ai::Brain;
+use crate::world::ai::BrainDef;
+use crate::world::Polyline;
+
+/// Handle to an entity. The generation makes sure a handle to a despawned entity doesn't start pointing at whatever
+/// got spawned into its slot afterwards.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EntityId {
+	index: u32,
+	generation: u32,
+}
+
+/// One kind of component, for every entity that has it. Indexed by entity index, so lookups are just a bounds check.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Components<T> {
+	slots: Vec<Option<(u32, T)>>,
+}
+
+impl<T> Default for Components<T> {
+	fn default() -> Self {
+		Components { slots: Vec::new() }
+	}
+}
+
+impl<T> Components<T> {
+	pub fn insert(&mut self, id: EntityId, component: T) {
+		let index = id.index as usize;
+		if self.slots.len() <= index {
+			self.slots.resize_with(index + 1, || None);
+		}
+		self.slots[index] = Some((id.generation, component));
+	}
+
+	pub fn remove(&mut self, id: EntityId) -> Option<T> {
+		let slot = self.slots.get_mut(id.index as usize)?;
+		match slot {
+			Some((generation, _)) if *generation == id.generation => slot.take().map(|(_, c)| c),
+			_ => None,
+		}
+	}
+
+	pub fn get(&self, id: EntityId) -> Option<&T> {
+		match self.slots.get(id.index as usize)? {
+			Some((generation, component)) if *generation == id.generation => Some(component),
+			_ => None,
+		}
+	}
+
+	pub fn get_mut(&mut self, id: EntityId) -> Option<&mut T> {
+		match self.slots.get_mut(id.index as usize)? {
+			Some((generation, component)) if *generation == id.generation => Some(component),
+			_ => None,
+		}
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (EntityId, &T)> {
+		self.slots
+			.iter()
+			.enumerate()
+			.filter_map(|(index, slot)| slot.as_ref().map(|(generation, c)| (EntityId { index: index as u32, generation: *generation }, c)))
+	}
+
+	pub fn iter_mut(&mut self) -> impl Iterator<Item = (EntityId, &mut T)> {
+		self.slots
+			.iter_mut()
+			.enumerate()
+			.filter_map(|(index, slot)| slot.as_mut().map(|(generation, c)| (EntityId { index: index as u32, generation: *generation }, c)))
+	}
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Transform {
+	#[serde(with = "crate::util::NotVec2")]
+	pub position: Vec2,
+	/// Radians, clockwise (since y points down).
+	pub rotation: f32,
+	pub scale: f32,
+}
+
+impl Transform {
+	pub fn at(position: Vec2) -> Self {
+		Transform { position, rotation: 0.0, scale: 1.0 }
+	}
+
+	pub fn apply(&self, local: Vec2) -> Vec2 {
+		let mut rotated = local * self.scale;
+		Rotor2::from_angle(self.rotation).rotate_vec(&mut rotated);
+		rotated + self.position
+	}
+
+	/// Unit vector pointing the way the entity faces. Unrotated entities face up.
+	pub fn forward(&self) -> Vec2 {
+		self.apply(Vec2::new(0.0, -1.0)) - self.apply(Vec2::zero())
+	}
+}
+
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Velocity {
+	#[serde(with = "crate::util::NotVec2")]
+	pub linear: Vec2,
+	/// Radians per second.
+	#[serde(default)]
+	pub angular: f32,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Tags(pub Vec<String>);
+
+impl Tags {
+	pub fn has(&self, tag: &str) -> bool {
+		self.0.iter().any(|t| t == tag)
+	}
+}
+
+/// An entity's shape, in its local space. Shapes don't change after spawn, so the key the renderer batches copies by
+/// gets worked out once here instead of every frame. Saved as just the polyline.
+#[derive(Clone, Deserialize)]
+#[serde(from = "Polyline")]
+pub struct Shape {
+	polyline: Polyline,
+	key: ShapeKey,
+}
+
+impl From<Polyline> for Shape {
+	fn from(polyline: Polyline) -> Self {
+		Shape { key: ShapeKey::new(&polyline), polyline }
+	}
+}
+
+impl Serialize for Shape {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		self.polyline.serialize(serializer)
+	}
+}
+
+impl Shape {
+	pub fn polyline(&self) -> &Polyline {
+		&self.polyline
+	}
+
+	pub fn key(&self) -> &ShapeKey {
+		&self.key
+	}
+}
+
+/// The raw bits of the points, color and thickness, not a hash, so different shapes can't collide.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ShapeKey(Vec<u32>);
+
+impl ShapeKey {
+	fn new(shape: &Polyline) -> Self {
+		let mut bits = Vec::with_capacity(shape.points.len() * 2 + 4);
+		for point in &shape.points {
+			bits.push(point.x.to_bits());
+			bits.push(point.y.to_bits());
+		}
+		bits.extend_from_slice(&[shape.color.x.to_bits(), shape.color.y.to_bits(), shape.color.z.to_bits(), shape.thickness.to_bits()]);
+		ShapeKey(bits)
+	}
+}
+
+/// All the dynamic stuff in the world.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Entities {
+	generations: Vec<u32>,
+	alive: Vec<bool>,
+	free: Vec<u32>,
+	pub transforms: Components<Transform>,
+	pub velocities: Components<Velocity>,
+	pub shapes: Components<Shape>,
+	pub tags: Components<Tags>,
+	/// Only matters for things that push and pull on entities, like ropes. Entities without one can't be moved by them.
+	#[serde(default)]
+	pub masses: Components<f32>,
+	/// Enemies. See `ai`.
+	#[serde(default)]
+	pub brains: Components<Brain>,
+}
+
+impl Entities {
+	pub fn spawn(&mut self) -> EntityId {
+		match self.free.pop() {
+			Some(index) => {
+				let i = index as usize;
+				self.alive[i] = true;
+				EntityId { index, generation: self.generations[i] }
+			},
+			None => {
+				self.generations.push(0);
+				self.alive.push(true);
+				EntityId { index: (self.generations.len() - 1) as u32, generation: 0 }
+			},
+		}
+	}
+
+	pub fn spawn_from_def(&mut self, def: &EntityDef) -> EntityId {
+		let id = self.spawn();
+		self.transforms.insert(id, Transform { position: def.position, rotation: def.rotation, scale: def.scale });
+		if let Some(velocity) = def.velocity {
+			self.velocities.insert(id, velocity);
+		}
+		if let Some(shape) = &def.shape {
+			self.shapes.insert(id, Shape::from(shape.clone()));
+		}
+		if !def.tags.is_empty() {
+			self.tags.insert(id, Tags(def.tags.clone()));
+		}
+		if let Some(mass) = def.mass {
+			self.masses.insert(id, mass);
+		}
+		id
+	}
+
+	pub fn despawn(&mut self, id: EntityId) {
+		if !self.is_alive(id) {
+			return;
+		}
+
+		self.transforms.remove(id);
+		self.velocities.remove(id);
+		self.shapes.remove(id);
+		self.tags.remove(id);
+		self.masses.remove(id);
+		self.brains.remove(id);
+
+		let i = id.index as usize;
+		self.alive[i] = false;
+		self.generations[i] = self.generations[i].wrapping_add(1);
+		self.free.push(id.index);
+	}
+
+	pub fn is_alive(&self, id: EntityId) -> bool {
+		let i = id.index as usize;
+		i < self.alive.len() && self.alive[i] && self.generations[i] == id.generation
+	}
+
+	pub fn ids(&self) -> impl Iterator<Item = EntityId> + '_ {
+		self.alive.iter().enumerate().filter(|(_, alive)| **alive).map(move |(i, _)| EntityId { index: i as u32, generation: self.generations[i] })
+	}
+
+	/// 1 / mass, or 0 for entities with no mass, which means they can't be pushed around.
+	pub fn inverse_mass(&self, id: EntityId) -> f32 {
+		match self.masses.get(id) {
+			Some(&mass) if mass > 0.0 => 1.0 / mass,
+			_ => 0.0,
+		}
+	}
+
+	pub fn has_tag(&self, id: EntityId, tag: &str) -> bool {
+		matches!(self.tags.get(id), Some(tags) if tags.has(tag))
+	}
+
+	pub fn with_tag<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = EntityId> + 'a {
+		self.tags.iter().filter(move |(_, tags)| tags.has(tag)).map(|(id, _)| id)
+	}
+
+	/// Radius of a circle around the entity's position that contains its whole shape.
+	pub fn bounding_radius(&self, id: EntityId) -> f32 {
+		let scale = self.transforms.get(id).map_or(1.0, |t| t.scale);
+		self.shapes.get(id).map(Shape::polyline).map_or(0.0, |shape| shape.points.iter().map(|p| p.mag()).fold(0.0, f32::max) * scale + shape.thickness / 2.0)
+	}
+
+	/// The entity's shape, moved into world space.
+	pub fn world_polyline(&self, id: EntityId) -> Option<Polyline> {
+		let shape = self.shapes.get(id)?.polyline();
+
+		let transform = self.transforms.get(id)?;
+		let points = shape.points.iter().map(|p| transform.apply(*p)).collect();
+		Some(Polyline::new(points, shape.color, shape.thickness * transform.scale))
+	}
+
+	/// Every entity's shape, moved into world space, ready for tessellation.
+	pub fn world_polylines(&self) -> Vec<Polyline> {
+		self.shapes.iter().filter_map(|(id, _)| self.world_polyline(id)).collect()
+	}
+}
+
+/// How an entity is written in a map file.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EntityDef {
+	#[serde(with = "crate::util::NotVec2")]
+	pub position: Vec2,
+	#[serde(default)]
+	pub rotation: f32,
+	#[serde(default = "one")]
+	pub scale: f32,
+	#[serde(default)]
+	pub velocity: Option<Velocity>,
+	#[serde(default)]
+	pub shape: Option<Polyline>,
+	#[serde(default)]
+	pub tags: Vec<String>,
+	#[serde(default)]
+	pub mass: Option<f32>,
+	/// Makes it an enemy. Brains get added by `GameWorld::from_map`, since the kinds of enemy are in their own file.
+	#[serde(default)]
+	pub brain: Option<BrainDef>,
+}
+
+fn one() -> f32 {
+	1.0
+}
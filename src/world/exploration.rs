@@ -0,0 +1,62 @@
+//! Which big square cells the player has been near, for the minimap.
+
+use std::collections::BTreeSet;
+
+use serde::Deserialize;
+use serde::Serialize;
+use ultraviolet::Vec2;
+
+pub type Cell = (i32, i32);
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Exploration {
+	cells: BTreeSet<Cell>,
+	/// The cell the player was in last time, so standing still doesn't redo the reveal every tick.
+	#[serde(skip)]
+	last_cell: Option<Cell>,
+}
+
+impl Exploration {
+	pub const CELL_SIZE: f32 = 64.0;
+	pub const REVEAL_RADIUS: f32 = 320.0;
+
+	pub fn cell_of(point: Vec2) -> Cell {
+		((point.x / Self::CELL_SIZE).floor() as i32, (point.y / Self::CELL_SIZE).floor() as i32)
+	}
+
+	/// The corner of a cell with the smallest coordinates.
+	pub fn origin_of(cell: Cell) -> Vec2 {
+		Vec2::new(cell.0 as f32, cell.1 as f32) * Self::CELL_SIZE
+	}
+
+	/// Marks everything around `position` as explored. Measured from the middle of its cell rather than the exact
+	/// position, so skipping the reveal while it stays in the same cell doesn't change what gets revealed.
+	pub fn reveal(&mut self, position: Vec2) {
+		let here = Self::cell_of(position);
+		if self.last_cell == Some(here) {
+			return;
+		}
+		self.last_cell = Some(here);
+		let middle = Self::origin_of(here) + Vec2::broadcast(Self::CELL_SIZE / 2.0);
+
+		let reach = (Self::REVEAL_RADIUS / Self::CELL_SIZE).ceil() as i32;
+		for x in here.0 - reach..=here.0 + reach {
+			for y in here.1 - reach..=here.1 + reach {
+				let center = Self::origin_of((x, y)) + Vec2::broadcast(Self::CELL_SIZE / 2.0);
+				if (center - middle).mag() <= Self::REVEAL_RADIUS {
+					self.cells.insert((x, y));
+				}
+			}
+		}
+	}
+
+	pub fn is_explored(&self, cell: Cell) -> bool {
+		self.cells.contains(&cell)
+	}
+
+	/// Explored cells that overlap the box from `min` to `max`, in order (by x, then y).
+	pub fn cells_in(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = Cell> + '_ {
+		let (min, max) = (Self::cell_of(min), Self::cell_of(max));
+		self.cells.range((min.0, i32::MIN)..=(max.0, i32::MAX)).copied().filter(move |cell| cell.1 >= min.1 && cell.1 <= max.1)
+	}
+}
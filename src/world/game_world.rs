@@ -1,19 +1,184 @@
 use anyhow::*;
+use ultraviolet::Vec2;
 
 use crate::asset_loader::AssetLoader;
+use crate::world::ai::Brain;
+use crate::world::ai::EnemyTypes;
+use crate::world::cave_gen::CaveGenerator;
+use crate::world::cave_gen::CaveParams;
+use crate::world::chunks::ChunkStreamer;
+use crate::world::chunks::StreamingDef;
+use crate::world::exploration::Exploration;
+use crate::world::gravity::GravitySource;
+use crate::world::nav::NavGrid;
+use crate::world::rope::Rope;
+use crate::world::rope::RopeDef;
+use crate::world::rope::RopeEnd;
+use crate::world::trigger::Trigger;
+use crate::world::Decal;
+use crate::world::Entities;
+use crate::world::EntityDef;
+use crate::world::LineId;
+use crate::world::Lines;
 use crate::world::Polyline;
 
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct GameWorld {
+	pub lines: Lines,
+	pub entities: Entities,
+	pub triggers: Vec<Trigger>,
+	/// For endless maps. Owns some of the lines in `lines`.
+	#[serde(default)]
+	pub streaming: Option<ChunkStreamer>,
+	#[serde(default)]
+	pub ropes: Vec<Rope>,
+	#[serde(default)]
+	pub gravity: Vec<GravitySource>,
+	/// Where the player has been, for the minimap.
+	#[serde(default)]
+	pub explored: Exploration,
+	/// Pictures under the lines.
+	#[serde(default)]
+	pub decals: Vec<Decal>,
+	/// One per agent size that's asked for a path. They only cache things about `lines`, so they don't need saving.
+	#[serde(skip)]
+	nav_grids: Vec<NavGrid>,
+}
+
+/// What's actually written in a map file. Entities are declared as `EntityDef`s and get spawned when the map is loaded.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct MapFile {
 	pub lines: Vec<Polyline>,
+	#[serde(default)]
+	pub entities: Vec<EntityDef>,
+	#[serde(default)]
+	pub triggers: Vec<Trigger>,
+	/// Path to a `CaveParams` file. If there is one, a cave gets generated (from the game's seed) and added to the map.
+	#[serde(default)]
+	pub cave: Option<String>,
+	/// Makes the map endless, with cave chunks generated (from the game's seed) as the player goes down.
+	#[serde(default)]
+	pub streaming: Option<StreamingDef>,
+	/// Tied up after the entities are spawned, so they can be tied to them.
+	#[serde(default)]
+	pub ropes: Vec<RopeDef>,
+	#[serde(default)]
+	pub gravity: Vec<GravitySource>,
+	#[serde(default)]
+	pub decals: Vec<Decal>,
 }
 
 impl GameWorld {
-	pub fn load(asset_loader: &AssetLoader) -> Result<Self> {
-		let world_file = asset_loader.load_string("world/map.ron")?;
+	pub const DEFAULT_MAP: &'static str = "world/map.ron";
+	/// Ropes made at runtime get about one point per this many units of length.
+	pub const ROPE_SEGMENT_LENGTH: f32 = 12.0;
+
+	pub fn load(asset_loader: &AssetLoader, map: &str, seed: u64) -> Result<Self> {
+		let world_file = asset_loader.load_string(map)?;
+
+		let mut map_file: MapFile = ron::from_str(&world_file).with_context(|| anyhow!("couldnt parse world file"))?;
+
+		if let Some(cave) = &map_file.cave {
+			let params = CaveParams::load(asset_loader, cave)?;
+			map_file.lines.extend(CaveGenerator::new(params, seed).generate());
+		}
+
+		let streaming = match map_file.streaming.take() {
+			Some(def) => {
+				let params = CaveParams::load(asset_loader, &def.cave)?;
+				Some(ChunkStreamer::new(def, params, seed))
+			},
+			None => None,
+		};
 
-		let world: GameWorld = ron::from_str(&world_file).with_context(|| anyhow!("couldnt parse world file"))?;
+		//only maps with enemies need the enemy file
+		let enemies = if map_file.entities.iter().any(|def| def.brain.is_some()) { EnemyTypes::load(asset_loader)? } else { EnemyTypes::default() };
 
+		let mut world = GameWorld::from_map(map_file, &enemies)?;
+		world.streaming = streaming;
+		world.stream();
 		Ok(world)
 	}
+
+	pub fn from_map(map_file: MapFile, enemies: &EnemyTypes) -> Result<Self> {
+		let mut entities = Entities::default();
+		for def in &map_file.entities {
+			let id = entities.spawn_from_def(def);
+			if let Some(brain) = &def.brain {
+				let params = enemies.0.get(&brain.kind).ok_or_else(|| anyhow!("no enemy type called '{}'", brain.kind))?;
+				entities.brains.insert(id, Brain::new(params.clone(), def.position, brain.patrol.clone()));
+			}
+		}
+
+		let ropes = map_file.ropes.iter().map(|def| Rope::from_def(def, &entities)).collect::<Result<_>>()?;
+
+		Ok(GameWorld {
+			lines: map_file.lines.into_iter().collect(),
+			entities,
+			triggers: map_file.triggers,
+			streaming: None,
+			ropes,
+			gravity: map_file.gravity,
+			explored: Exploration::default(),
+			decals: map_file.decals,
+			nav_grids: Vec::new(),
+		})
+	}
+
+	/// Where the action is. Chunks get streamed in around here, and the camera looks at it.
+	pub fn focus(&self) -> Option<Vec2> {
+		let player = self.entities.with_tag("player").next()?;
+		self.entities.transforms.get(player).map(|t| t.position)
+	}
+
+	/// How fast the focus is moving.
+	pub fn focus_velocity(&self) -> Vec2 {
+		let player = self.entities.with_tag("player").next();
+		player.and_then(|id| self.entities.velocities.get(id)).map_or(Vec2::zero(), |v| v.linear)
+	}
+
+	/// Streams chunks in and out around the focus, if this is an endless map. Returns whether any lines changed.
+	pub fn stream(&mut self) -> bool {
+		let focus_y = self.focus().map_or(0.0, |focus| focus.y);
+		match &mut self.streaming {
+			Some(streamer) => streamer.update(focus_y, &mut self.lines),
+			None => false,
+		}
+	}
+
+	/// Ties a rope between two things, starting out straight. Ends tied to entities start at the entity.
+	pub fn attach_rope(&mut self, start: RopeEnd, end: RopeEnd, length: f32, color: ultraviolet::Vec3, thickness: f32) {
+		let position = |end: RopeEnd| match end {
+			RopeEnd::Free => None,
+			RopeEnd::Anchor(position) => Some(position),
+			RopeEnd::Entity(id) => self.entities.transforms.get(id).map(|t| t.position),
+		};
+		let from = position(start).or_else(|| position(end)).unwrap_or_else(Vec2::zero);
+		let to = position(end).unwrap_or(from);
+
+		let segments = (length / Self::ROPE_SEGMENT_LENGTH).ceil() as u32;
+		self.ropes.push(Rope::new((start, from), (end, to), length, segments, color, thickness));
+	}
+
+	/// Cuts every rope tied to this entity.
+	pub fn detach_ropes(&mut self, id: crate::world::EntityId) {
+		self.ropes.retain(|rope| !rope.is_attached_to(id));
+	}
+
+	/// Waypoints for an agent this big to get from `from` to `to` without touching any lines, starting at `from`.
+	pub fn find_path(&mut self, from: Vec2, to: Vec2, agent_radius: f32) -> Option<Vec<Vec2>> {
+		let index = match self.nav_grids.iter().position(|grid| grid.agent_radius == agent_radius) {
+			Some(index) => index,
+			None => {
+				self.nav_grids.push(NavGrid::new(agent_radius));
+				self.nav_grids.len() - 1
+			},
+		};
+		self.nav_grids[index].find_path(&self.lines, from, to)
+	}
+
+	/// Which streamed chunk a line belongs to, if any.
+	pub fn chunk_of(&self, id: LineId) -> Option<i64> {
+		self.streaming.as_ref()?.chunk_of(id)
+	}
 }
@@ -0,0 +1,102 @@
+//! Point masses and uniform fields. Applied as a half kick either side of `systems::integrate` (velocity Verlet), so
+//! orbits don't spiral outwards.
+
+use serde::Deserialize;
+use serde::Serialize;
+use ultraviolet::Vec2;
+use ultraviolet::Vec3;
+
+use crate::world::raycast::Ray;
+use crate::world::trigger::TriggerShape;
+use crate::world::Entities;
+use crate::world::Lines;
+use crate::world::Polyline;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum GravitySource {
+	/// Pulls towards `center`, falling off with distance squared. An orbit at distance `r` goes at
+	/// `sqrt(strength / r)` units per second.
+	Well {
+		#[serde(with = "crate::util::NotVec2")]
+		center: Vec2,
+		strength: f32,
+		/// Inside this distance the pull stops getting stronger, so things going right through the middle don't get
+		/// flung off to infinity.
+		#[serde(default)]
+		core_radius: f32,
+		/// Beyond this distance there's no pull at all. No limit if it's not set.
+		#[serde(default)]
+		range: Option<f32>,
+	},
+	/// The same pull everywhere inside `region` (or everywhere, if there's no region).
+	Field {
+		#[serde(with = "crate::util::NotVec2")]
+		acceleration: Vec2,
+		#[serde(default)]
+		region: Option<TriggerShape>,
+	},
+}
+
+impl GravitySource {
+	pub fn acceleration_at(&self, point: Vec2) -> Vec2 {
+		match self {
+			GravitySource::Well { center, strength, core_radius, range } => {
+				let offset = *center - point;
+				let distance_sq = offset.mag_sq();
+				if distance_sq <= f32::EPSILON || matches!(range, Some(range) if distance_sq > range * range) {
+					return Vec2::zero();
+				}
+
+				let distance = distance_sq.sqrt();
+				offset / distance * (strength / distance_sq.max(core_radius * core_radius))
+			},
+			GravitySource::Field { acceleration, region } => match region {
+				Some(region) if !region.contains(point) => Vec2::zero(),
+				_ => *acceleration,
+			},
+		}
+	}
+}
+
+/// Total pull from every source at this point.
+pub fn acceleration_at(sources: &[GravitySource], point: Vec2) -> Vec2 {
+	sources.iter().fold(Vec2::zero(), |total, source| total + source.acceleration_at(point))
+}
+
+/// Speeds up everything with a velocity by `dt` worth of gravity. Call with half a tick before `systems::integrate`
+/// and half a tick after.
+pub fn kick(sources: &[GravitySource], entities: &mut Entities, dt: f32) {
+	if sources.is_empty() {
+		return;
+	}
+
+	for (id, velocity) in entities.velocities.iter_mut() {
+		if let Some(transform) = entities.transforms.get(id) {
+			velocity.linear += acceleration_at(sources, transform.position) * dt;
+		}
+	}
+}
+
+/// Where something starting here at this velocity will go over the next `ticks` ticks, stepped exactly the way the
+/// game steps it, losing `drag` of its velocity per second like the player's ship does. Collisions aren't simulated,
+/// the path just stops at the first thing it would hit. Good for aim guides: set the color and thickness to taste.
+pub fn predict_trajectory(sources: &[GravitySource], lines: &Lines, mut position: Vec2, mut velocity: Vec2, drag: f32, ticks: u32, dt: f32) -> Polyline {
+	let mut points = vec![position];
+
+	for _ in 0..ticks {
+		velocity *= (1.0 - drag * dt).max(0.0);
+		velocity += acceleration_at(sources, position) * (dt / 2.0);
+		let next = position + velocity * dt;
+		velocity += acceleration_at(sources, next) * (dt / 2.0);
+
+		if let Some(hit) = lines.raycast(&Ray::between(position, next)) {
+			points.push(hit.point);
+			break;
+		}
+
+		position = next;
+		points.push(position);
+	}
+
+	Polyline::new(points, Vec3::one(), 1.0)
+}
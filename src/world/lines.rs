@@ -0,0 +1,167 @@
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+use std::iter::FromIterator;
+
+use serde::Deserialize;
+use serde::Serialize;
+use ultraviolet::Vec2;
+
+use crate::world::Polyline;
+use crate::world::SpatialIndex;
+
+/// Which line in `Lines`. Stays the same until the line is removed, then might get reused.
+pub type LineId = usize;
+
+/// All the static lines in the world, in slots that don't move around when lines get removed, plus a spatial index
+/// over their segments that's kept in sync. The index isn't saved, it gets rebuilt on load. Also remembers which lines
+/// changed, so the renderer only has to re-tessellate those.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(from = "LineSlots")]
+pub struct Lines {
+	slots: Vec<Option<Polyline>>,
+	free: Vec<LineId>,
+	#[serde(skip)]
+	index: SpatialIndex,
+	#[serde(skip)]
+	changed: BTreeSet<LineId>,
+	/// Counts every insert and remove.
+	#[serde(skip)]
+	revision: u64,
+	/// Boxes around the last few changes, with the revision each one made. For things that cache stuff about areas
+	/// of the world and need to know which areas to forget.
+	#[serde(skip)]
+	change_log: VecDeque<(u64, Vec2, Vec2)>,
+}
+
+/// `Lines` as it's saved.
+#[derive(Deserialize)]
+struct LineSlots {
+	slots: Vec<Option<Polyline>>,
+	free: Vec<LineId>,
+}
+
+impl From<LineSlots> for Lines {
+	fn from(saved: LineSlots) -> Self {
+		let mut lines = Lines { slots: saved.slots, free: saved.free, ..Default::default() };
+		lines.rebuild_index();
+		lines
+	}
+}
+
+impl Lines {
+	/// How many changes `change_log` remembers. Anything that falls further behind than that has to start over.
+	const CHANGE_LOG_LENGTH: usize = 1024;
+
+	pub fn insert(&mut self, line: Polyline) -> LineId {
+		let id = match self.free.pop() {
+			Some(id) => id,
+			None => {
+				self.slots.push(None);
+				self.slots.len() - 1
+			},
+		};
+
+		self.index.insert(id, &line);
+		self.log_change(&line);
+		self.slots[id] = Some(line);
+		self.changed.insert(id);
+		id
+	}
+
+	pub fn remove(&mut self, id: LineId) -> Option<Polyline> {
+		let line = self.slots.get_mut(id)?.take()?;
+		self.index.remove(id, &line);
+		self.free.push(id);
+		self.changed.insert(id);
+		self.log_change(&line);
+		Some(line)
+	}
+
+	pub fn get(&self, id: LineId) -> Option<&Polyline> {
+		self.slots.get(id)?.as_ref()
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (LineId, &Polyline)> {
+		self.slots.iter().enumerate().filter_map(|(id, slot)| slot.as_ref().map(|line| (id, line)))
+	}
+
+	pub fn len(&self) -> usize {
+		self.slots.len() - self.free.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// (line, segment index) pairs for segments that might be within `radius` of `center`, thickness included.
+	pub fn segments_near(&self, center: Vec2, radius: f32) -> Vec<(LineId, u32)> {
+		self.index.query_circle(center, radius)
+	}
+
+	/// (line, segment index) pairs for segments that a ray might hit before it's gone `max_distance`.
+	pub fn segments_along(&self, origin: Vec2, direction: Vec2, max_distance: f32) -> Vec<(LineId, u32)> {
+		self.index.query_ray(origin, direction, max_distance)
+	}
+
+	/// Lines that might be within `radius` of `center`, thickness included.
+	pub fn lines_near(&self, center: Vec2, radius: f32) -> Vec<LineId> {
+		let mut ids: Vec<LineId> = self.segments_near(center, radius).into_iter().map(|(id, _)| id).collect();
+		ids.dedup();
+		ids
+	}
+
+	/// A box around every line, thickness included. `None` if there aren't any.
+	pub fn bounds(&self) -> Option<(Vec2, Vec2)> {
+		self.iter().map(|(_, line)| line.bounds()).fold(None, |total, (min, max)| match total {
+			Some((total_min, total_max)) => Some((min.min_by_component(total_min), max.max_by_component(total_max))),
+			None => Some((min, max)),
+		})
+	}
+
+	/// Lines that were added or removed (or both) since the last call.
+	pub fn take_changed(&mut self) -> BTreeSet<LineId> {
+		std::mem::take(&mut self.changed)
+	}
+
+	fn log_change(&mut self, line: &Polyline) {
+		self.revision += 1;
+		let (min, max) = line.bounds();
+		self.change_log.push_back((self.revision, min, max));
+		if self.change_log.len() > Self::CHANGE_LOG_LENGTH {
+			self.change_log.pop_front();
+		}
+	}
+
+	/// Goes up by one for every line added or removed.
+	pub fn revision(&self) -> u64 {
+		self.revision
+	}
+
+	/// Boxes around everything that changed after `revision`, or `None` if that was too long ago to remember.
+	pub fn changes_since(&self, revision: u64) -> Option<impl Iterator<Item = (Vec2, Vec2)> + '_> {
+		let oldest_remembered = self.change_log.front().map_or(self.revision, |(r, _, _)| r - 1);
+		if revision < oldest_remembered {
+			return None;
+		}
+		Some(self.change_log.iter().filter(move |(r, _, _)| *r > revision).map(|(_, min, max)| (*min, *max)))
+	}
+
+	pub fn rebuild_index(&mut self) {
+		self.index.clear();
+		for (id, slot) in self.slots.iter().enumerate() {
+			if let Some(line) = slot {
+				self.index.insert(id, line);
+			}
+		}
+	}
+}
+
+impl FromIterator<Polyline> for Lines {
+	fn from_iter<I: IntoIterator<Item = Polyline>>(iter: I) -> Self {
+		let mut lines = Lines::default();
+		for line in iter {
+			lines.insert(line);
+		}
+		lines
+	}
+}
@@ -1,5 +1,25 @@
+pub mod ai;
+pub mod cave_gen;
+pub mod chunks;
+mod decal;
+pub mod destruction;
+mod entity;
+pub mod exploration;
 mod game_world;
+pub mod gravity;
+mod lines;
+pub mod nav;
 mod polyline;
+pub mod raycast;
+pub mod rope;
+mod spatial_index;
+pub mod steering;
+pub mod systems;
+pub mod trigger;
 
+pub use decal::*;
+pub use entity::*;
 pub use game_world::*;
+pub use lines::*;
 pub use polyline::*;
+pub use spatial_index::*;
@@ -0,0 +1,246 @@
+//! Grid pathfinding through the world's lines. Cells are checked lazily and forgotten when lines near them change.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
+
+use ultraviolet::Vec2;
+
+use crate::world::Lines;
+
+type Cell = (i32, i32);
+
+/// Walkability of the world's cells, for agents of one size.
+pub struct NavGrid {
+	pub agent_radius: f32,
+	open: HashMap<Cell, bool>,
+	/// The `Lines` revision `open` is up to date with.
+	revision: u64,
+}
+
+impl NavGrid {
+	pub const CELL_SIZE: f32 = 16.0;
+	/// How many cells a search can look at before giving up. Open space goes on forever, so unreachable goals
+	/// need a limit.
+	pub const MAX_SEARCH: usize = 20_000;
+	/// Forget everything past this many cells, so wandering agents don't fill up memory.
+	pub const MAX_CACHED: usize = 200_000;
+
+	pub fn new(agent_radius: f32) -> Self {
+		NavGrid { agent_radius, open: HashMap::new(), revision: 0 }
+	}
+
+	fn cell_of(point: Vec2) -> Cell {
+		((point.x / Self::CELL_SIZE).floor() as i32, (point.y / Self::CELL_SIZE).floor() as i32)
+	}
+
+	fn center_of(cell: Cell) -> Vec2 {
+		Vec2::new(cell.0 as f32 + 0.5, cell.1 as f32 + 0.5) * Self::CELL_SIZE
+	}
+
+	/// Forgets about cells near lines that changed since last time.
+	pub fn update(&mut self, lines: &Lines) {
+		if self.revision == lines.revision() {
+			return;
+		}
+
+		match lines.changes_since(self.revision) {
+			Some(changes) => {
+				let pad = Vec2::broadcast(self.agent_radius + Self::CELL_SIZE);
+				for (min, max) in changes {
+					let (min_cell, max_cell) = (Self::cell_of(min - pad), Self::cell_of(max + pad));
+					let area = (max_cell.0 - min_cell.0 + 1) as usize * (max_cell.1 - min_cell.1 + 1) as usize;
+
+					//for big changes, going through what's cached is quicker than going through every cell
+					if area > self.open.len() {
+						self.open.retain(|cell, _| cell.0 < min_cell.0 || cell.0 > max_cell.0 || cell.1 < min_cell.1 || cell.1 > max_cell.1);
+					} else {
+						for x in min_cell.0..=max_cell.0 {
+							for y in min_cell.1..=max_cell.1 {
+								self.open.remove(&(x, y));
+							}
+						}
+					}
+				}
+			},
+			None => self.open.clear(),
+		}
+
+		self.revision = lines.revision();
+	}
+
+	fn is_open(&mut self, lines: &Lines, cell: Cell) -> bool {
+		let radius = self.agent_radius;
+		*self.open.entry(cell).or_insert_with(|| {
+			let center = Self::center_of(cell);
+			lines.segments_near(center, radius).into_iter().all(|(id, segment)| match lines.get(id) {
+				Some(line) => line.closest_point_on_segment(segment as usize, center).0 > radius + line.thickness / 2.0,
+				None => true,
+			})
+		})
+	}
+
+	/// The closest open cell to `point`, looking a few cells out.
+	fn nearest_open(&mut self, lines: &Lines, point: Vec2) -> Option<Cell> {
+		let start = Self::cell_of(point);
+		for ring in 0..4 {
+			let mut candidates: Vec<Cell> = (-ring..=ring)
+				.flat_map(|x| (-ring..=ring).map(move |y| (start.0 + x, start.1 + y)))
+				.filter(|(x, y)| (x - start.0).abs() == ring || (y - start.1).abs() == ring)
+				.collect();
+			candidates.sort_by(|a, b| (Self::center_of(*a) - point).mag_sq().partial_cmp(&(Self::center_of(*b) - point).mag_sq()).unwrap());
+			if let Some(cell) = candidates.into_iter().find(|cell| self.is_open(lines, *cell)) {
+				return Some(cell);
+			}
+		}
+		None
+	}
+
+	/// A* from `from` to `to`, then smoothed into as few waypoints as possible. The first waypoint is `from` and the
+	/// last is `to`. `None` if there's no way through (or it's too far to find).
+	pub fn find_path(&mut self, lines: &Lines, from: Vec2, to: Vec2) -> Option<Vec<Vec2>> {
+		self.update(lines);
+		if self.open.len() > Self::MAX_CACHED {
+			self.open.clear();
+		}
+
+		//short hops don't need a search
+		if clear_path(lines, from, to, self.agent_radius) {
+			return Some(vec![from, to]);
+		}
+
+		let start = self.nearest_open(lines, from)?;
+		let goal = self.nearest_open(lines, to)?;
+		let cells = self.search(lines, start, goal)?;
+
+		let mut path = vec![from];
+		path.extend(cells.iter().skip(1).take(cells.len().saturating_sub(2)).map(|cell| Self::center_of(*cell)));
+		path.push(to);
+		Some(smooth_path(lines, &path, self.agent_radius))
+	}
+
+	fn search(&mut self, lines: &Lines, start: Cell, goal: Cell) -> Option<Vec<Cell>> {
+		let heuristic = |cell: Cell| {
+			//octile distance, in cells
+			let (dx, dy) = ((cell.0 - goal.0).abs() as f32, (cell.1 - goal.1).abs() as f32);
+			dx.max(dy) + (std::f32::consts::SQRT_2 - 1.0) * dx.min(dy)
+		};
+
+		let mut open = BinaryHeap::new();
+		let mut came_from: HashMap<Cell, Cell> = HashMap::new();
+		let mut cost: HashMap<Cell, f32> = HashMap::new();
+		open.push(Candidate { cell: start, estimate: heuristic(start) });
+		cost.insert(start, 0.0);
+
+		while let Some(Candidate { cell, .. }) = open.pop() {
+			if cell == goal {
+				let mut path = vec![goal];
+				while let Some(&previous) = came_from.get(path.last().unwrap()) {
+					path.push(previous);
+				}
+				path.reverse();
+				return Some(path);
+			}
+			if cost.len() > Self::MAX_SEARCH {
+				return None;
+			}
+
+			let here = cost[&cell];
+			for &(dx, dy) in &[(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)] {
+				let next = (cell.0 + dx, cell.1 + dy);
+				if !self.is_open(lines, next) {
+					continue;
+				}
+				//no cutting corners past walls
+				if dx != 0 && dy != 0 && !(self.is_open(lines, (cell.0 + dx, cell.1)) && self.is_open(lines, (cell.0, cell.1 + dy))) {
+					continue;
+				}
+
+				let step = if dx != 0 && dy != 0 { std::f32::consts::SQRT_2 } else { 1.0 };
+				let next_cost = here + step;
+				if matches!(cost.get(&next), Some(&old) if old <= next_cost) {
+					continue;
+				}
+				cost.insert(next, next_cost);
+				came_from.insert(next, cell);
+				open.push(Candidate { cell: next, estimate: next_cost + heuristic(next) });
+			}
+		}
+
+		None
+	}
+}
+
+/// Entry in the A* queue. Ordered backwards, so the `BinaryHeap` pops the lowest estimate first.
+struct Candidate {
+	cell: Cell,
+	estimate: f32,
+}
+
+impl PartialEq for Candidate {
+	fn eq(&self, other: &Self) -> bool {
+		self.cmp(other) == Ordering::Equal
+	}
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for Candidate {
+	fn cmp(&self, other: &Self) -> Ordering {
+		//ties broken by cell, so searches come out the same every time
+		other.estimate.partial_cmp(&self.estimate).unwrap_or(Ordering::Equal).then_with(|| other.cell.cmp(&self.cell))
+	}
+}
+
+/// Skips waypoints whenever there's a clear straight run past them ("string pulling").
+pub fn smooth_path(lines: &Lines, path: &[Vec2], agent_radius: f32) -> Vec<Vec2> {
+	if path.len() <= 2 {
+		return path.to_vec();
+	}
+
+	let mut smoothed = vec![path[0]];
+	let mut current = 0;
+	while current < path.len() - 1 {
+		//furthest waypoint that can be reached in a straight line
+		let next = (current + 1..path.len()).rev().find(|&i| clear_path(lines, path[current], path[i], agent_radius)).unwrap_or(current + 1);
+		smoothed.push(path[next]);
+		current = next;
+	}
+	smoothed
+}
+
+/// Whether an agent this big could go in a straight line between these points without touching any line.
+pub fn clear_path(lines: &Lines, from: Vec2, to: Vec2, agent_radius: f32) -> bool {
+	let center = (from + to) / 2.0;
+	let reach = (to - from).mag() / 2.0 + agent_radius;
+
+	lines.segments_near(center, reach).into_iter().all(|(id, segment)| match lines.get(id) {
+		Some(line) => {
+			let segment = segment as usize;
+			segment_distance(from, to, line.points[segment], line.points[segment + 1]) > agent_radius + line.thickness / 2.0
+		},
+		None => true,
+	})
+}
+
+/// Shortest distance between two line segments.
+fn segment_distance(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> f32 {
+	let cross = |o: Vec2, p: Vec2, q: Vec2| (p.x - o.x) * (q.y - o.y) - (p.y - o.y) * (q.x - o.x);
+	let crosses = (cross(a, b, c) > 0.0) != (cross(a, b, d) > 0.0) && (cross(c, d, a) > 0.0) != (cross(c, d, b) > 0.0);
+	if crosses {
+		return 0.0;
+	}
+
+	let point_to_segment = |p: Vec2, s: Vec2, e: Vec2| {
+		let se = e - s;
+		let h = if se.mag_sq() > f32::EPSILON { ((p - s).dot(se) / se.mag_sq()).clamp(0.0, 1.0) } else { 0.0 };
+		(p - (s + se * h)).mag()
+	};
+	point_to_segment(a, c, d).min(point_to_segment(b, c, d)).min(point_to_segment(c, a, b)).min(point_to_segment(d, a, b))
+}
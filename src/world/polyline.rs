@@ -1,7 +1,8 @@
+use ultraviolet::Lerp;
 use ultraviolet::Vec2;
 use ultraviolet::Vec3;
 
-#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct Polyline {
 	#[serde(with = "crate::util::vec_of_vec2")]
 	pub points: Vec<Vec2>,
@@ -31,6 +32,32 @@ impl Polyline {
 			.sqrt()
 	}
 
+	/// Like `distance_to`, but also says where the closest point on the line is.
+	pub fn closest_point(&self, point: Vec2) -> (f32, Vec2) {
+		let (dist_sq, closest) = self
+			.points
+			.array_windows()
+			.map(|&[a, b]| {
+				let pa = point - a;
+				let ba = b - a;
+				let h = (pa.dot(ba) / ba.dot(ba)).clamp(0.0, 1.0);
+				((pa - ba * h).mag_sq(), a + ba * h)
+			})
+			.fold((f32::INFINITY, point), |best, next| if next.0 < best.0 { next } else { best });
+
+		(dist_sq.sqrt(), closest)
+	}
+
+	/// Like `closest_point`, but only looks at the segment from `points[segment]` to `points[segment + 1]`.
+	pub fn closest_point_on_segment(&self, segment: usize, point: Vec2) -> (f32, Vec2) {
+		let (a, b) = (self.points[segment], self.points[segment + 1]);
+		let pa = point - a;
+		let ba = b - a;
+		let h = (pa.dot(ba) / ba.dot(ba)).clamp(0.0, 1.0);
+		let closest = a + ba * h;
+		((point - closest).mag(), closest)
+	}
+
 	pub fn contains(&self, point: Vec2) -> bool {
 		self.points.array_windows().any(|&[a, b]| {
 			let pa = point - a;
@@ -44,3 +71,297 @@ impl Polyline {
 		})
 	}
 }
+
+/// Geometry operations. These all make a new polyline with the same color and thickness, and leave the old one alone.
+/// Closed polylines (where the last point is the first point again) stay closed.
+impl Polyline {
+	fn with_points(&self, points: Vec<Vec2>) -> Polyline {
+		Polyline::new(points, self.color, self.thickness)
+	}
+
+	pub fn is_closed(&self) -> bool {
+		self.points.len() > 2 && (self.points[0] - self.points[self.points.len() - 1]).mag_sq() <= f32::EPSILON
+	}
+
+	/// Corners of a box around the whole line, thickness included.
+	pub fn bounds(&self) -> (Vec2, Vec2) {
+		let pad = Vec2::broadcast(self.thickness / 2.0);
+		let first = self.points.first().copied().unwrap_or_else(Vec2::zero);
+		let (min, max) = self.points.iter().fold((first, first), |(min, max), p| (min.min_by_component(*p), max.max_by_component(*p)));
+		(min - pad, max + pad)
+	}
+
+	/// Total length along all the segments.
+	pub fn length(&self) -> f32 {
+		self.points.array_windows().map(|&[a, b]| (b - a).mag()).sum()
+	}
+
+	/// Ramer-Douglas-Peucker. Drops points until the line would move by more than `tolerance` anywhere. The ends stay put.
+	pub fn simplified(&self, tolerance: f32) -> Polyline {
+		if self.points.len() < 3 {
+			return self.clone();
+		}
+
+		let mut keep = vec![false; self.points.len()];
+		keep[0] = true;
+		keep[self.points.len() - 1] = true;
+
+		//stack instead of recursion, cave walls can have a lot of points
+		let mut spans = vec![(0, self.points.len() - 1)];
+		while let Some((first, last)) = spans.pop() {
+			let (a, b) = (self.points[first], self.points[last]);
+			let farthest =
+				(first + 1..last)
+					.map(|i| (i, distance_to_segment(self.points[i], a, b)))
+					.fold((first, 0.0), |best, next| if next.1 > best.1 { next } else { best });
+
+			if farthest.1 > tolerance {
+				keep[farthest.0] = true;
+				spans.push((first, farthest.0));
+				spans.push((farthest.0, last));
+			}
+		}
+
+		let points = self.points.iter().zip(keep).filter(|(_, keep)| *keep).map(|(point, _)| *point).collect();
+		self.with_points(points)
+	}
+
+	/// Chaikin corner cutting. Each iteration replaces every corner with two points a quarter of the way along its
+	/// segments, so the line gets rounder (and shorter) without ever going outside the original. Open lines keep their ends.
+	pub fn smoothed_chaikin(&self, iterations: u32) -> Polyline {
+		let closed = self.is_closed();
+		let mut points = self.points.clone();
+
+		for _ in 0..iterations {
+			if points.len() < 3 {
+				break;
+			}
+
+			let mut next = Vec::with_capacity(points.len() * 2);
+			if !closed {
+				next.push(points[0]);
+			}
+			for &[a, b] in points.array_windows() {
+				next.push(a.lerp(b, 0.25));
+				next.push(a.lerp(b, 0.75));
+			}
+			if closed {
+				next.push(next[0]);
+			} else {
+				//cutting the end segments would pull the ends in, so put them back
+				next.remove(1);
+				next.pop();
+				next.push(points[points.len() - 1]);
+			}
+			points = next;
+		}
+
+		self.with_points(points)
+	}
+
+	/// Catmull-Rom spline through every point, with `subdivisions` extra points on each segment. Unlike Chaikin, the
+	/// line still goes through all the original points.
+	pub fn smoothed_catmull_rom(&self, subdivisions: u32) -> Polyline {
+		let n = self.points.len();
+		if n < 3 {
+			return self.clone();
+		}
+
+		let closed = self.is_closed();
+		//the point `offset` away from `i`, wrapping around closed lines (skipping the repeated end) and clamping open ones
+		let neighbour = |i: usize, offset: isize| -> Vec2 {
+			let j = i as isize + offset;
+			if closed {
+				self.points[j.rem_euclid(n as isize - 1) as usize]
+			} else {
+				self.points[j.clamp(0, n as isize - 1) as usize]
+			}
+		};
+
+		let mut points = Vec::with_capacity((n - 1) * (subdivisions as usize + 1) + 1);
+		for i in 0..n - 1 {
+			let (p0, p1, p2, p3) = (neighbour(i, -1), self.points[i], self.points[i + 1], neighbour(i, 2));
+			for step in 0..=subdivisions {
+				let t = step as f32 / (subdivisions + 1) as f32;
+				let (t2, t3) = (t * t, t * t * t);
+				let point = (p1 * 2.0 + (p2 - p0) * t + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2 + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3) * 0.5;
+				points.push(point);
+			}
+		}
+		points.push(self.points[n - 1]);
+
+		self.with_points(points)
+	}
+
+	/// Points spaced evenly along the line, `spacing` apart (the last gap might be shorter). Corners between the samples get
+	/// cut off.
+	pub fn resampled(&self, spacing: f32) -> Polyline {
+		if self.points.len() < 2 || spacing <= 0.0 {
+			return self.clone();
+		}
+
+		let mut points = vec![self.points[0]];
+		//how far along the current segment the next sample goes
+		let mut next_at = spacing;
+		for &[a, b] in self.points.array_windows() {
+			let segment_length = (b - a).mag();
+			while next_at <= segment_length {
+				points.push(a.lerp(b, next_at / segment_length));
+				next_at += spacing;
+			}
+			next_at -= segment_length;
+		}
+
+		let last = self.points[self.points.len() - 1];
+		if (points[points.len() - 1] - last).mag() > spacing * 0.01 {
+			points.push(last);
+		}
+
+		self.with_points(points)
+	}
+
+	/// Drops points that are within `epsilon` of the one before them.
+	pub fn without_duplicates(&self, epsilon: f32) -> Polyline {
+		let mut points: Vec<Vec2> = Vec::with_capacity(self.points.len());
+		for &point in &self.points {
+			match points.last() {
+				Some(&previous) if (point - previous).mag() <= epsilon => (),
+				_ => points.push(point),
+			}
+		}
+
+		//keep the real end, rather than whichever point was close to it
+		if let (Some(last), Some(&real_last)) = (points.last_mut(), self.points.last()) {
+			*last = real_last;
+		}
+
+		self.with_points(points)
+	}
+
+	/// Drops points that don't make a corner: ones within `tolerance` of the straight line between their neighbours.
+	/// Duplicates count as collinear too.
+	pub fn without_collinear(&self, tolerance: f32) -> Polyline {
+		if self.points.len() < 3 {
+			return self.clone();
+		}
+
+		let mut points = vec![self.points[0]];
+		for i in 1..self.points.len() - 1 {
+			let previous = points[points.len() - 1];
+			if distance_to_segment(self.points[i], previous, self.points[i + 1]) > tolerance {
+				points.push(self.points[i]);
+			}
+		}
+		points.push(self.points[self.points.len() - 1]);
+
+		self.with_points(points)
+	}
+}
+
+/// Whether a point is inside a polygon. The polygon doesn't need to repeat its first point at the end.
+pub fn polygon_contains(points: &[Vec2], point: Vec2) -> bool {
+	//count how many edges a ray going off to the right crosses
+	let mut inside = false;
+	let mut j = points.len().wrapping_sub(1);
+	for i in 0..points.len() {
+		let (a, b) = (points[i], points[j]);
+		if (a.y > point.y) != (b.y > point.y) && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x {
+			inside = !inside;
+		}
+		j = i;
+	}
+	inside
+}
+
+fn distance_to_segment(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+	let pa = point - a;
+	let ba = b - a;
+	let length_sq = ba.mag_sq();
+	if length_sq <= f32::EPSILON {
+		return pa.mag();
+	}
+	let h = (pa.dot(ba) / length_sq).clamp(0.0, 1.0);
+	(pa - ba * h).mag()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn line(points: &[(f32, f32)]) -> Polyline {
+		Polyline::new(points.iter().map(|&(x, y)| Vec2::new(x, y)).collect(), Vec3::one(), 1.0)
+	}
+
+	fn square() -> Polyline {
+		line(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0), (0.0, 0.0)])
+	}
+
+	fn zigzag() -> Polyline {
+		line(&[(0.0, 0.0), (1.0, 0.1), (2.0, -0.1), (3.0, 5.0), (4.0, 0.0), (5.0, 0.05), (6.0, 0.0)])
+	}
+
+	fn same_ends(before: &Polyline, after: &Polyline) {
+		assert_eq!(after.points.first(), before.points.first());
+		assert_eq!(after.points.last(), before.points.last());
+	}
+
+	#[test]
+	fn simplified() {
+		let simple = zigzag().simplified(0.5);
+		same_ends(&zigzag(), &simple);
+		assert_eq!(simple.points, vec![Vec2::new(0.0, 0.0), Vec2::new(2.0, -0.1), Vec2::new(3.0, 5.0), Vec2::new(4.0, 0.0), Vec2::new(6.0, 0.0)]);
+
+		assert!(square().simplified(0.5).is_closed());
+		assert_eq!(line(&[(0.0, 0.0), (1.0, 1.0)]).simplified(0.5).points.len(), 2);
+		assert_eq!(line(&[(3.0, 3.0), (3.0, 3.0), (3.0, 3.0)]).simplified(0.5).points.len(), 2);
+	}
+
+	#[test]
+	fn smoothed_chaikin() {
+		let smooth = zigzag().smoothed_chaikin(3);
+		same_ends(&zigzag(), &smooth);
+		assert!(smooth.points.len() > zigzag().points.len());
+		assert!(smooth.length() < zigzag().length());
+
+		let smooth_square = square().smoothed_chaikin(2);
+		assert!(smooth_square.is_closed());
+		assert!(!smooth_square.points.contains(&Vec2::new(10.0, 10.0)));
+
+		let short = line(&[(0.0, 0.0), (1.0, 1.0)]);
+		assert_eq!(short.smoothed_chaikin(4).points, short.points);
+		assert_eq!(line(&[]).smoothed_chaikin(4).points, vec![]);
+	}
+
+	#[test]
+	fn resampled() {
+		let straight = line(&[(0.0, 0.0), (10.0, 0.0)]).resampled(3.0);
+		assert_eq!(straight.points, vec![Vec2::new(0.0, 0.0), Vec2::new(3.0, 0.0), Vec2::new(6.0, 0.0), Vec2::new(9.0, 0.0), Vec2::new(10.0, 0.0)]);
+
+		let resampled = zigzag().resampled(0.5);
+		same_ends(&zigzag(), &resampled);
+		for &[a, b] in resampled.points.array_windows() {
+			assert!((b - a).mag() <= 0.5 + 1e-4);
+		}
+
+		assert!(square().resampled(3.0).is_closed());
+
+		//zero length segments and spacings shouldn't make NaNs or loop forever
+		let stuttering = line(&[(0.0, 0.0), (0.0, 0.0), (4.0, 0.0), (4.0, 0.0)]).resampled(1.0);
+		assert_eq!(stuttering.points.len(), 5);
+		assert!(stuttering.points.iter().all(|p| p.x.is_finite() && p.y.is_finite()));
+		assert_eq!(zigzag().resampled(0.0).points, zigzag().points);
+		assert_eq!(line(&[(1.0, 1.0)]).resampled(1.0).points, vec![Vec2::new(1.0, 1.0)]);
+	}
+
+	#[test]
+	fn without_collinear() {
+		let straightened = line(&[(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (2.0, 0.0), (2.0, 2.0), (2.0, 4.0)]).without_collinear(0.01);
+		assert_eq!(straightened.points, vec![Vec2::new(0.0, 0.0), Vec2::new(2.0, 0.0), Vec2::new(2.0, 4.0)]);
+
+		let with_midpoints = line(&[(0.0, 0.0), (5.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0), (0.0, 0.0)]).without_collinear(0.01);
+		assert!(with_midpoints.is_closed());
+		assert_eq!(with_midpoints.points.len(), 5);
+
+		assert_eq!(line(&[(0.0, 0.0), (0.0, 0.0)]).without_collinear(0.01).points.len(), 2);
+	}
+}
@@ -0,0 +1,252 @@
+//! Rays against the world's lines. Lines count as having their thickness, each segment as a capsule `thickness` wide.
+
+use ultraviolet::Vec2;
+
+use crate::world::LineId;
+use crate::world::Lines;
+
+/// A ray starting at `origin`, going `max_distance` along `direction`.
+#[derive(Copy, Clone, Debug)]
+pub struct Ray {
+	pub origin: Vec2,
+	/// Normalized.
+	pub direction: Vec2,
+	pub max_distance: f32,
+}
+
+impl Ray {
+	/// A zero `direction` has nowhere to point, so it gets the same made up one as `between` uses.
+	pub fn new(origin: Vec2, direction: Vec2, max_distance: f32) -> Self {
+		let length = direction.mag();
+		let direction = if length > f32::EPSILON { direction / length } else { Vec2::new(1.0, 0.0) };
+		Ray { origin, direction, max_distance }
+	}
+
+	/// The ray from `from` that stops at `to`.
+	pub fn between(from: Vec2, to: Vec2) -> Self {
+		let offset = to - from;
+		let distance = offset.mag();
+		let direction = if distance > f32::EPSILON { offset / distance } else { Vec2::new(1.0, 0.0) };
+		Ray { origin: from, direction, max_distance: distance }
+	}
+
+	pub fn at(&self, distance: f32) -> Vec2 {
+		self.origin + self.direction * distance
+	}
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct RayHit {
+	/// How far along the ray the hit is.
+	pub distance: f32,
+	pub point: Vec2,
+	/// Unit vector pointing out of the surface that got hit, back towards the ray.
+	pub normal: Vec2,
+	pub line: LineId,
+	/// Which segment of the line, the one from `points[segment]` to `points[segment + 1]`.
+	pub segment: usize,
+}
+
+impl Lines {
+	/// The first thing the ray hits, if it hits anything.
+	pub fn raycast(&self, ray: &Ray) -> Option<RayHit> {
+		self.hits(ray).min_by(|a, b| a.distance.total_cmp(&b.distance))
+	}
+
+	/// Everything the ray hits, nearest first. A ray through a wall hits every segment it passes through, including
+	/// both segments where two of them meet at a corner.
+	pub fn raycast_all(&self, ray: &Ray) -> Vec<RayHit> {
+		let mut hits: Vec<RayHit> = self.hits(ray).collect();
+		hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+		hits
+	}
+
+	/// The first thing between `from` and `to`.
+	pub fn segment_cast(&self, from: Vec2, to: Vec2) -> Option<RayHit> {
+		self.raycast(&Ray::between(from, to))
+	}
+
+	/// Whether nothing is in the way between these two points.
+	pub fn line_of_sight(&self, from: Vec2, to: Vec2) -> bool {
+		self.segment_cast(from, to).is_none()
+	}
+
+	fn hits<'a>(&'a self, ray: &'a Ray) -> impl Iterator<Item = RayHit> + 'a {
+		self.segments_along(ray.origin, ray.direction, ray.max_distance).into_iter().filter_map(move |(line_id, segment)| {
+			let line = self.get(line_id)?;
+			let segment = segment as usize;
+			let (a, b) = (line.points[segment], line.points[segment + 1]);
+
+			//NaN from a broken ray or line isn't a hit, and can't be sorted with the real ones
+			let (distance, normal) = ray_capsule(ray, a, b, line.thickness / 2.0).filter(|(distance, _)| distance.is_finite())?;
+			Some(RayHit { distance, point: ray.at(distance), normal, line: line_id, segment })
+		})
+	}
+}
+
+/// Distance along the ray to where it first touches the capsule around `a`-`b`, and the normal there. Rays that start
+/// inside hit straight away.
+fn ray_capsule(ray: &Ray, a: Vec2, b: Vec2, radius: f32) -> Option<(f32, Vec2)> {
+	let ba = b - a;
+	let length = ba.mag();
+
+	//starting inside
+	let h = if length > f32::EPSILON { ((ray.origin - a).dot(ba) / (length * length)).clamp(0.0, 1.0) } else { 0.0 };
+	let from_closest = ray.origin - (a + ba * h);
+	if from_closest.mag_sq() <= radius * radius {
+		let normal = if from_closest.mag_sq() > f32::EPSILON { from_closest.normalized() } else { -ray.direction };
+		return Some((0.0, normal));
+	}
+
+	let mut best: Option<(f32, Vec2)> = None;
+	let mut consider = |distance: f32, normal: Vec2| {
+		if distance < 0.0 || distance > ray.max_distance {
+			return;
+		}
+		match best {
+			Some((closer, _)) if closer <= distance => (),
+			_ => best = Some((distance, normal)),
+		}
+	};
+
+	//the two flat sides
+	if length > f32::EPSILON {
+		let along = ba / length;
+		let across = Vec2::new(-along.y, along.x);
+		for &side in &[-1.0, 1.0] {
+			let normal = across * side;
+			let facing = ray.direction.dot(normal);
+			if facing >= 0.0 {
+				continue;
+			}
+
+			let distance = (a + normal * radius - ray.origin).dot(normal) / facing;
+			let along_segment = (ray.at(distance) - a).dot(along);
+			if (0.0..=length).contains(&along_segment) {
+				consider(distance, normal);
+			}
+		}
+	}
+
+	//and the round ends. lines with no thickness don't have any
+	for &center in &[a, b] {
+		if radius <= f32::EPSILON {
+			break;
+		}
+
+		let to_origin = ray.origin - center;
+		let half_b = to_origin.dot(ray.direction);
+		let c = to_origin.mag_sq() - radius * radius;
+		let discriminant = half_b * half_b - c;
+		if discriminant < 0.0 {
+			continue;
+		}
+
+		let distance = -half_b - discriminant.sqrt();
+		let normal = (ray.at(distance) - center) / radius;
+		consider(distance, normal);
+	}
+
+	best
+}
+
+#[cfg(test)]
+mod tests {
+	use ultraviolet::Vec3;
+
+	use super::*;
+	use crate::world::Polyline;
+
+	fn line(points: &[(f32, f32)], thickness: f32) -> Polyline {
+		Polyline::new(points.iter().map(|&(x, y)| Vec2::new(x, y)).collect(), Vec3::one(), thickness)
+	}
+
+	fn assert_close(actual: f32, expected: f32) {
+		assert!((actual - expected).abs() < 1e-4, "expected {}, got {}", expected, actual);
+	}
+
+	fn assert_near(actual: Vec2, expected: Vec2) {
+		assert!((actual - expected).mag() < 1e-4, "expected {:?}, got {:?}", expected, actual);
+	}
+
+	#[test]
+	fn thickness_and_normals() {
+		let mut lines = Lines::default();
+		let floor = lines.insert(line(&[(-10.0, 0.0), (10.0, 0.0)], 4.0));
+
+		//from above and below, each hitting the side facing it
+		let hit = lines.raycast(&Ray::new(Vec2::new(0.0, -10.0), Vec2::new(0.0, 1.0), 100.0)).unwrap();
+		assert_eq!((hit.line, hit.segment), (floor, 0));
+		assert_close(hit.distance, 8.0);
+		assert_near(hit.point, Vec2::new(0.0, -2.0));
+		assert_near(hit.normal, Vec2::new(0.0, -1.0));
+
+		let hit = lines.raycast(&Ray::new(Vec2::new(3.0, 10.0), Vec2::new(0.0, -1.0), 100.0)).unwrap();
+		assert_near(hit.point, Vec2::new(3.0, 2.0));
+		assert_near(hit.normal, Vec2::new(0.0, 1.0));
+
+		//the round end sticks out past the last point
+		let hit = lines.raycast(&Ray::new(Vec2::new(-20.0, 0.0), Vec2::new(1.0, 0.0), 100.0)).unwrap();
+		assert_close(hit.distance, 8.0);
+		assert_near(hit.normal, Vec2::new(-1.0, 0.0));
+
+		//too short to get there, or going the other way
+		assert!(lines.raycast(&Ray::new(Vec2::new(0.0, -10.0), Vec2::new(0.0, 1.0), 7.0)).is_none());
+		assert!(lines.raycast(&Ray::new(Vec2::new(0.0, -10.0), Vec2::new(0.0, -1.0), 100.0)).is_none());
+		assert!(lines.line_of_sight(Vec2::new(-20.0, -3.0), Vec2::new(20.0, -3.0)));
+		assert!(!lines.line_of_sight(Vec2::new(-20.0, -1.0), Vec2::new(20.0, -1.0)));
+	}
+
+	#[test]
+	fn starting_inside() {
+		let mut lines = Lines::default();
+		lines.insert(line(&[(-10.0, 0.0), (10.0, 0.0)], 4.0));
+
+		let hit = lines.raycast(&Ray::new(Vec2::new(0.0, 1.0), Vec2::new(1.0, 0.0), 100.0)).unwrap();
+		assert_eq!(hit.distance, 0.0);
+		assert_near(hit.point, Vec2::new(0.0, 1.0));
+		assert_near(hit.normal, Vec2::new(0.0, 1.0));
+
+		//right on the line there's no way out, so it points back along the ray
+		let hit = lines.raycast(&Ray::new(Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), 100.0)).unwrap();
+		assert_eq!(hit.distance, 0.0);
+		assert_near(hit.normal, Vec2::new(-1.0, 0.0));
+	}
+
+	#[test]
+	fn corners_hit_both_segments() {
+		let mut lines = Lines::default();
+		let corner = lines.insert(line(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)], 2.0));
+
+		let hits = lines.raycast_all(&Ray::between(Vec2::new(20.0, -10.0), Vec2::new(0.0, 10.0)));
+		assert_eq!(hits.len(), 2);
+		let mut segments: Vec<_> = hits.iter().map(|hit| (hit.line, hit.segment)).collect();
+		segments.sort_by_key(|&(_, segment)| segment);
+		assert_eq!(segments, vec![(corner, 0), (corner, 1)]);
+
+		let expected = 200f32.sqrt() - 1.0;
+		for hit in &hits {
+			assert_close(hit.distance, expected);
+			assert_near(hit.normal, Vec2::new(1.0, -1.0).normalized());
+		}
+	}
+
+	#[test]
+	fn raycast_all_is_nearest_first() {
+		let mut lines = Lines::default();
+		let far = lines.insert(line(&[(30.0, -10.0), (30.0, 10.0)], 2.0));
+		let near = lines.insert(line(&[(10.0, -10.0), (10.0, 10.0)], 2.0));
+		let middle = lines.insert(line(&[(20.0, -10.0), (20.0, 10.0)], 2.0));
+
+		let ray = Ray::new(Vec2::zero(), Vec2::new(1.0, 0.0), 100.0);
+		let hits = lines.raycast_all(&ray);
+		assert_eq!(hits.iter().map(|hit| hit.line).collect::<Vec<_>>(), vec![near, middle, far]);
+		for (hit, expected) in hits.iter().zip(&[9.0, 19.0, 29.0]) {
+			assert_close(hit.distance, *expected);
+		}
+		assert_eq!(lines.raycast(&ray).unwrap().line, near);
+
+		//and nothing past the end of the ray
+		assert_eq!(lines.raycast_all(&Ray::new(Vec2::zero(), Vec2::new(1.0, 0.0), 25.0)).len(), 2);
+	}
+}
@@ -0,0 +1,215 @@
+//! Verlet ropes. Ends can be pinned in place or tied to an entity.
+
+use anyhow::*;
+use serde::Deserialize;
+use serde::Serialize;
+use ultraviolet::Vec2;
+use ultraviolet::Vec3;
+
+use crate::world::gravity;
+use crate::world::gravity::GravitySource;
+use crate::world::Entities;
+use crate::world::EntityId;
+use crate::world::Lines;
+use crate::world::Polyline;
+
+/// Only for maps without any gravity sources, so ropes still hang.
+pub const ROPE_GRAVITY: f32 = 400.0;
+pub const ROPE_DAMPING: f32 = 0.99;
+/// More is stiffer, and slower.
+pub const ROPE_ITERATIONS: u32 = 12;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RopeEnd {
+	Free,
+	Anchor(#[serde(with = "crate::util::NotVec2")] Vec2),
+	/// Pulls on the entity if it has a mass, otherwise just follows it.
+	Entity(EntityId),
+}
+
+/// Entities are picked by tag, since they don't have ids in map files.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RopeEndDef {
+	Free(#[serde(with = "crate::util::NotVec2")] Vec2),
+	Anchor(#[serde(with = "crate::util::NotVec2")] Vec2),
+	Tagged(String),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RopeDef {
+	pub from: RopeEndDef,
+	pub to: RopeEndDef,
+	pub length: f32,
+	#[serde(default = "default_segments")]
+	pub segments: u32,
+	#[serde(with = "crate::util::NotVec3")]
+	pub color: Vec3,
+	pub thickness: f32,
+}
+
+fn default_segments() -> u32 {
+	16
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct RopePoint {
+	#[serde(with = "crate::util::NotVec2")]
+	pub position: Vec2,
+	/// Verlet has no velocity, it's how far the point moved since this.
+	#[serde(with = "crate::util::NotVec2")]
+	pub previous: Vec2,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Rope {
+	pub points: Vec<RopePoint>,
+	pub segment_length: f32,
+	pub start: RopeEnd,
+	pub end: RopeEnd,
+	#[serde(with = "crate::util::NotVec3")]
+	pub color: Vec3,
+	pub thickness: f32,
+}
+
+impl Rope {
+	pub fn new((start, from): (RopeEnd, Vec2), (end, to): (RopeEnd, Vec2), length: f32, segments: u32, color: Vec3, thickness: f32) -> Self {
+		let segments = segments.max(1);
+		let points = (0..=segments)
+			.map(|i| {
+				let position = from + (to - from) * (i as f32 / segments as f32);
+				RopePoint { position, previous: position }
+			})
+			.collect();
+
+		Rope { points, segment_length: length / segments as f32, start, end, color, thickness }
+	}
+
+	/// The entities have to be spawned already.
+	pub fn from_def(def: &RopeDef, entities: &Entities) -> Result<Self> {
+		let resolve = |end: &RopeEndDef| -> Result<(RopeEnd, Vec2)> {
+			match end {
+				RopeEndDef::Free(position) => Ok((RopeEnd::Free, *position)),
+				RopeEndDef::Anchor(position) => Ok((RopeEnd::Anchor(*position), *position)),
+				RopeEndDef::Tagged(tag) => entities
+					.with_tag(tag)
+					.find_map(|id| entities.transforms.get(id).map(|t| (RopeEnd::Entity(id), t.position)))
+					.ok_or_else(|| anyhow!("no entity tagged '{}' to tie a rope to", tag)),
+			}
+		};
+
+		Ok(Rope::new(resolve(&def.from)?, resolve(&def.to)?, def.length, def.segments, def.color, def.thickness))
+	}
+
+	pub fn polyline(&self) -> Polyline {
+		Polyline::new(self.points.iter().map(|p| p.position).collect(), self.color, self.thickness)
+	}
+
+	pub fn is_attached_to(&self, id: EntityId) -> bool {
+		self.start == RopeEnd::Entity(id) || self.end == RopeEnd::Entity(id)
+	}
+}
+
+/// Where an end is pinned, and how easily the rope moves it (0 is not at all).
+fn pin_for(end: RopeEnd, entities: &Entities) -> Option<(Vec2, f32)> {
+	match end {
+		RopeEnd::Free => None,
+		RopeEnd::Anchor(position) => Some((position, 0.0)),
+		RopeEnd::Entity(id) => entities.transforms.get(id).map(|t| (t.position, entities.inverse_mass(id))),
+	}
+}
+
+pub fn simulate_ropes(ropes: &mut [Rope], entities: &mut Entities, lines: &Lines, gravity: &[GravitySource], dt: f32) {
+	for rope in ropes.iter_mut() {
+		//ends tied to despawned entities come loose
+		for end in [&mut rope.start, &mut rope.end].iter_mut() {
+			if let RopeEnd::Entity(id) = **end {
+				if !entities.is_alive(id) {
+					**end = RopeEnd::Free;
+				}
+			}
+		}
+
+		let last = rope.points.len() - 1;
+		let start_pin = pin_for(rope.start, entities);
+		let end_pin = pin_for(rope.end, entities);
+
+		//rope points weigh 1. how easily each point can be moved by a constraint
+		let mut inverse_masses = vec![1.0; rope.points.len()];
+		for &(index, pin) in &[(0, start_pin), (last, end_pin)] {
+			if let Some((position, inverse_mass)) = pin {
+				rope.points[index].position = position;
+				rope.points[index].previous = position;
+				inverse_masses[index] = inverse_mass;
+			}
+		}
+		let pinned_at = [start_pin.map(|(p, _)| p), end_pin.map(|(p, _)| p)];
+
+		for (index, point) in rope.points.iter_mut().enumerate() {
+			if (index == 0 && start_pin.is_some()) || (index == last && end_pin.is_some()) {
+				continue;
+			}
+			let velocity = (point.position - point.previous) * ROPE_DAMPING;
+			point.previous = point.position;
+			let acceleration = if gravity.is_empty() { Vec2::new(0.0, ROPE_GRAVITY) } else { gravity::acceleration_at(gravity, point.position) };
+			point.position += velocity + acceleration * dt * dt;
+		}
+
+		for _ in 0..ROPE_ITERATIONS {
+			for i in 0..last {
+				let (weight_a, weight_b) = (inverse_masses[i], inverse_masses[i + 1]);
+				if weight_a + weight_b <= 0.0 {
+					continue;
+				}
+
+				let delta = rope.points[i + 1].position - rope.points[i].position;
+				let distance = delta.mag();
+				if distance <= f32::EPSILON {
+					continue;
+				}
+
+				let correction = delta * ((distance - rope.segment_length) / distance / (weight_a + weight_b));
+				rope.points[i].position += correction * weight_a;
+				rope.points[i + 1].position -= correction * weight_b;
+			}
+
+			collide_points(rope, &inverse_masses, lines);
+		}
+
+		//whatever the rope did to the ends gets passed on to the entities tied there
+		for &(index, end, pinned) in &[(0, rope.start, pinned_at[0]), (last, rope.end, pinned_at[1])] {
+			if let (RopeEnd::Entity(id), Some(pinned)) = (end, pinned) {
+				let pulled = rope.points[index].position - pinned;
+				if let Some(transform) = entities.transforms.get_mut(id) {
+					transform.position += pulled;
+				}
+				if let Some(velocity) = entities.velocities.get_mut(id) {
+					velocity.linear += pulled / dt;
+				}
+			}
+		}
+	}
+}
+
+/// So ropes drape over things instead of going through them.
+fn collide_points(rope: &mut Rope, inverse_masses: &[f32], lines: &Lines) {
+	let radius = rope.thickness / 2.0;
+
+	for (point, &inverse_mass) in rope.points.iter_mut().zip(inverse_masses) {
+		if inverse_mass <= 0.0 {
+			continue;
+		}
+
+		for (id, segment) in lines.segments_near(point.position, radius) {
+			let line = match lines.get(id) {
+				Some(line) => line,
+				None => continue,
+			};
+
+			let allowed = radius + line.thickness / 2.0;
+			let (distance, closest) = line.closest_point_on_segment(segment as usize, point.position);
+			if distance < allowed && distance > f32::EPSILON {
+				point.position = closest + (point.position - closest) / distance * allowed;
+			}
+		}
+	}
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use ultraviolet::Vec2;
+
+use crate::world::LineId;
+use crate::world::Polyline;
+
+/// Uniform grid over line segments, so collision and friends only have to look at the segments nearby instead of
+/// every segment in the world.
+#[derive(Default)]
+pub struct SpatialIndex {
+	cells: HashMap<(i32, i32), Vec<(LineId, u32)>>,
+}
+
+impl SpatialIndex {
+	pub const CELL_SIZE: f32 = 128.0;
+
+	fn cell_of(point: Vec2) -> (i32, i32) {
+		((point.x / Self::CELL_SIZE).floor() as i32, (point.y / Self::CELL_SIZE).floor() as i32)
+	}
+
+	/// Every cell touched by the box around this segment, fattened by the line's thickness.
+	fn cells_for_segment(line: &Polyline, segment: usize) -> impl Iterator<Item = (i32, i32)> {
+		let (a, b) = (line.points[segment], line.points[segment + 1]);
+		let pad = Vec2::broadcast(line.thickness / 2.0);
+		Self::cells_in(a.min_by_component(b) - pad, a.max_by_component(b) + pad)
+	}
+
+	fn cells_in(min: Vec2, max: Vec2) -> impl Iterator<Item = (i32, i32)> {
+		let (min_x, min_y) = Self::cell_of(min);
+		let (max_x, max_y) = Self::cell_of(max);
+		(min_x..=max_x).flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+	}
+
+	pub fn insert(&mut self, id: LineId, line: &Polyline) {
+		for segment in 0..line.points.len().saturating_sub(1) {
+			for cell in Self::cells_for_segment(line, segment) {
+				self.cells.entry(cell).or_default().push((id, segment as u32));
+			}
+		}
+	}
+
+	/// `line` has to be the same as it was when it was inserted, to find all the cells it was put in.
+	pub fn remove(&mut self, id: LineId, line: &Polyline) {
+		for segment in 0..line.points.len().saturating_sub(1) {
+			for cell in Self::cells_for_segment(line, segment) {
+				if let Some(entries) = self.cells.get_mut(&cell) {
+					entries.retain(|(entry_id, _)| *entry_id != id);
+					if entries.is_empty() {
+						self.cells.remove(&cell);
+					}
+				}
+			}
+		}
+	}
+
+	pub fn clear(&mut self) {
+		self.cells.clear();
+	}
+
+	/// (line, segment index) pairs for segments that might be inside this box. No duplicates.
+	pub fn query_box(&self, min: Vec2, max: Vec2) -> Vec<(LineId, u32)> {
+		let mut found: Vec<(LineId, u32)> = Self::cells_in(min, max).filter_map(|cell| self.cells.get(&cell)).flatten().copied().collect();
+		found.sort_unstable();
+		found.dedup();
+		found
+	}
+
+	/// Like `query_box`, but only the cells a ray passes through on its way out to `max_distance`, so long rays don't
+	/// have to look at everything in their bounding box. `direction` has to be normalized.
+	pub fn query_ray(&self, origin: Vec2, direction: Vec2, max_distance: f32) -> Vec<(LineId, u32)> {
+		let mut cell = Self::cell_of(origin);
+		let last = Self::cell_of(origin + direction * max_distance);
+
+		//walk the grid one cell boundary at a time (Amanatides & Woo)
+		let axis = |origin: f32, direction: f32, cell: i32| -> (i32, f32, f32) {
+			if direction > 0.0 {
+				(1, ((cell + 1) as f32 * Self::CELL_SIZE - origin) / direction, Self::CELL_SIZE / direction)
+			} else if direction < 0.0 {
+				(-1, (cell as f32 * Self::CELL_SIZE - origin) / direction, -Self::CELL_SIZE / direction)
+			} else {
+				(0, f32::INFINITY, f32::INFINITY)
+			}
+		};
+		let (step_x, mut next_x, delta_x) = axis(origin.x, direction.x, cell.0);
+		let (step_y, mut next_y, delta_y) = axis(origin.y, direction.y, cell.1);
+
+		let mut found = Vec::new();
+		loop {
+			if let Some(entries) = self.cells.get(&cell) {
+				found.extend_from_slice(entries);
+			}
+			if cell == last || next_x.min(next_y) > max_distance {
+				break;
+			}
+
+			if next_x < next_y {
+				cell.0 += step_x;
+				next_x += delta_x;
+			} else {
+				cell.1 += step_y;
+				next_y += delta_y;
+			}
+		}
+
+		found.sort_unstable();
+		found.dedup();
+		found
+	}
+
+	pub fn query_circle(&self, center: Vec2, radius: f32) -> Vec<(LineId, u32)> {
+		self.query_box(center - Vec2::broadcast(radius), center + Vec2::broadcast(radius))
+	}
+}
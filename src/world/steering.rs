@@ -0,0 +1,95 @@
+//! Steering behaviours (after Craig Reynolds). Each returns a force in units/s², add a few up and cap the total.
+
+use rand::Rng;
+use rand_pcg::Pcg32;
+use ultraviolet::Rotor2;
+use ultraviolet::Vec2;
+
+use crate::world::raycast::Ray;
+use crate::world::Lines;
+
+/// Head straight for `target` at full speed.
+pub fn seek(position: Vec2, velocity: Vec2, target: Vec2, max_speed: f32) -> Vec2 {
+	let offset = target - position;
+	if offset.mag_sq() <= f32::EPSILON {
+		return -velocity;
+	}
+	offset.normalized() * max_speed - velocity
+}
+
+/// Get away from `threat` at full speed.
+pub fn flee(position: Vec2, velocity: Vec2, threat: Vec2, max_speed: f32) -> Vec2 {
+	let offset = position - threat;
+	if offset.mag_sq() <= f32::EPSILON {
+		return Vec2::new(max_speed, 0.0) - velocity;
+	}
+	offset.normalized() * max_speed - velocity
+}
+
+/// Like `seek`, but slows down inside `slow_radius` so it stops on the target instead of overshooting.
+pub fn arrive(position: Vec2, velocity: Vec2, target: Vec2, max_speed: f32, slow_radius: f32) -> Vec2 {
+	let offset = target - position;
+	let distance = offset.mag();
+	if distance <= f32::EPSILON {
+		return -velocity;
+	}
+	let speed = if distance < slow_radius { max_speed * distance / slow_radius } else { max_speed };
+	offset / distance * speed - velocity
+}
+
+/// Ambles around. Keeps a target on a circle `distance` ahead of itself, and jitters where on the circle it is by up
+/// to `jitter` radians a tick. `angle` is that spot on the circle, and needs keeping between ticks.
+pub fn wander(velocity: Vec2, angle: &mut f32, rng: &mut Pcg32, radius: f32, distance: f32, jitter: f32, max_speed: f32) -> Vec2 {
+	*angle += rng.gen_range(-jitter..=jitter);
+
+	let heading = if velocity.mag_sq() > f32::EPSILON { velocity.normalized() } else { Vec2::new(0.0, -1.0) };
+	let mut on_circle = heading * radius;
+	Rotor2::from_angle(*angle).rotate_vec(&mut on_circle);
+
+	(heading * distance + on_circle).normalized() * max_speed - velocity
+}
+
+/// Steers away from world lines. Three feelers stick out ahead (further the faster it's going), and any that hit
+/// something push away from it, harder the closer the hit. Anything already within `radius` of a line gets pushed
+/// straight out too.
+pub fn avoid_obstacles(lines: &Lines, position: Vec2, velocity: Vec2, radius: f32, look_ahead: f32, max_force: f32) -> Vec2 {
+	let mut force = Vec2::zero();
+	let speed = velocity.mag();
+
+	if speed > f32::EPSILON {
+		let heading = velocity / speed;
+		let feeler_length = radius + look_ahead * (speed / (speed + 100.0));
+
+		for &(angle, weight) in &[(0.0f32, 1.0), (0.5, 0.6), (-0.5, 0.6)] {
+			let mut direction = heading;
+			Rotor2::from_angle(angle).rotate_vec(&mut direction);
+
+			if let Some(hit) = lines.raycast(&Ray::new(position, direction, feeler_length)) {
+				let urgency = 1.0 - hit.distance / feeler_length;
+				force += hit.normal * (max_force * urgency * weight);
+			}
+		}
+	}
+
+	for (id, segment) in lines.segments_near(position, radius) {
+		if let Some(line) = lines.get(id) {
+			let (distance, closest) = line.closest_point_on_segment(segment as usize, position);
+			let allowed = radius + line.thickness / 2.0;
+			if distance < allowed && distance > f32::EPSILON {
+				force += (position - closest) / distance * (max_force * (1.0 - distance / allowed));
+			}
+		}
+	}
+
+	force
+}
+
+/// Caps a vector's length.
+pub fn truncate(vector: Vec2, max: f32) -> Vec2 {
+	let length = vector.mag();
+	if length > max {
+		vector * (max / length)
+	} else {
+		vector
+	}
+}
@@ -0,0 +1,151 @@
+//! Systems that run over entity components. `Game::update` runs them once per tick, `dt` is the tick length in seconds.
+
+use ultraviolet::Vec2;
+
+use crate::debug_draw::DebugDraw;
+use crate::game::GameEvent;
+use crate::input::Actions;
+use crate::world::Entities;
+use crate::world::Lines;
+use crate::world::Polyline;
+
+/// Tuning for how the player's ship handles.
+pub const SHIP_THRUST: f32 = 400.0;
+pub const SHIP_TURN_SPEED: f32 = 4.0;
+/// Fraction of velocity lost per second.
+pub const SHIP_DRAG: f32 = 0.6;
+/// How fast the player gets flung away from hazards.
+pub const HAZARD_KNOCKBACK: f32 = 300.0;
+/// How fast something has to hit a wall to make a "bump". Slower than this is just scraping or resting against it.
+pub const BUMP_SPEED: f32 = 80.0;
+
+/// Turns and thrusts every entity tagged "player".
+pub fn player_control(entities: &mut Entities, actions: &Actions, dt: f32) {
+	let players: Vec<_> = entities.with_tag("player").collect();
+
+	for id in players {
+		let forward = match entities.transforms.get(id) {
+			Some(transform) => transform.forward(),
+			None => continue,
+		};
+
+		if let Some(velocity) = entities.velocities.get_mut(id) {
+			let turn = (actions.right as i32 - actions.left as i32) as f32;
+			velocity.angular = turn * SHIP_TURN_SPEED;
+
+			let thrust = (actions.up as i32 - actions.down as i32) as f32;
+			velocity.linear += forward * thrust * SHIP_THRUST * dt;
+			velocity.linear *= (1.0 - SHIP_DRAG * dt).max(0.0);
+		}
+	}
+}
+
+/// Moves everything with a velocity.
+pub fn integrate(entities: &mut Entities, dt: f32) {
+	for (id, velocity) in entities.velocities.iter() {
+		if let Some(transform) = entities.transforms.get_mut(id) {
+			transform.position += velocity.linear * dt;
+			transform.rotation += velocity.angular * dt;
+		}
+	}
+}
+
+/// Pushes moving entities back out of world lines (and entities tagged "solid"), and kills the part of their velocity
+/// going into the wall.
+pub fn collide_with_world(entities: &mut Entities, lines: &Lines, events: &mut Vec<GameEvent>, debug: &mut DebugDraw) {
+	let movers: Vec<_> = entities.velocities.iter().map(|(id, _)| id).collect();
+	let solids: Vec<_> = entities.with_tag("solid").filter_map(|id| entities.world_polyline(id)).collect();
+
+	for id in movers {
+		let radius = entities.bounding_radius(id);
+		let position = match entities.transforms.get(id) {
+			Some(transform) => transform.position,
+			None => continue,
+		};
+
+		//only the segments the spatial index says are nearby, since streamed caves have a lot of them
+		let nearby_segments =
+			lines.segments_near(position, radius).into_iter().filter_map(|(line_id, segment)| lines.get(line_id).map(|line| (line, Some(segment))));
+		let nearby: Vec<(&Polyline, Option<u32>)> = nearby_segments.chain(solids.iter().map(|line| (line, None))).collect();
+
+		for (line, segment) in nearby {
+			let transform = match entities.transforms.get_mut(id) {
+				Some(transform) => transform,
+				None => break,
+			};
+
+			let allowed = radius + line.thickness / 2.0;
+			let (distance, closest) = match segment {
+				Some(segment) => line.closest_point_on_segment(segment as usize, transform.position),
+				None => line.closest_point(transform.position),
+			};
+			if distance >= allowed || distance <= f32::EPSILON {
+				continue;
+			}
+
+			let normal = (transform.position - closest) / distance;
+			transform.position = closest + normal * allowed;
+			debug.arrow(closest, closest + normal * 24.0, DebugDraw::YELLOW);
+
+			if let Some(velocity) = entities.velocities.get_mut(id) {
+				let into_wall = velocity.linear.dot(normal);
+				if into_wall < 0.0 {
+					velocity.linear -= normal * into_wall;
+					if -into_wall >= BUMP_SPEED {
+						events.push(GameEvent::Named { name: "bump".into(), position: closest });
+					}
+				}
+			}
+		}
+	}
+}
+
+/// Lets players collect "pickup"s and get hurt by "hazard"s.
+pub fn touch_players(entities: &mut Entities, events: &mut Vec<GameEvent>) {
+	let players: Vec<_> = entities.with_tag("player").collect();
+
+	for player in players {
+		let player_pos = match entities.transforms.get(player) {
+			Some(transform) => transform.position,
+			None => continue,
+		};
+		let player_radius = entities.bounding_radius(player);
+
+		let touching: Vec<_> = entities
+			.transforms
+			.iter()
+			.filter(|(id, _)| *id != player)
+			.filter(|(id, transform)| (transform.position - player_pos).mag() < player_radius + entities.bounding_radius(*id))
+			.map(|(id, transform)| (id, transform.position))
+			.collect();
+
+		for (other, position) in touching {
+			if entities.has_tag(other, "pickup") {
+				entities.despawn(other);
+				events.push(GameEvent::Named { name: "pickup".into(), position });
+			} else if entities.has_tag(other, "hazard") {
+				//knock the player away, so it doesn't keep getting hurt every tick
+				if let Some(velocity) = entities.velocities.get_mut(player) {
+					//right on top of it there's no away, so bounce back the way it came, or anywhere if it's still
+					let offset = player_pos - position;
+					let away = if offset.mag_sq() > f32::EPSILON {
+						offset.normalized()
+					} else if velocity.linear.mag_sq() > f32::EPSILON {
+						-velocity.linear.normalized()
+					} else {
+						Vec2::new(1.0, 0.0)
+					};
+					velocity.linear = away * HAZARD_KNOCKBACK;
+				}
+				events.push(GameEvent::Named { name: "hurt".into(), position: player_pos });
+			}
+		}
+	}
+}
+
+/// Keeps angles from growing forever, so they stay precise and saves stay tidy.
+pub fn wrap_rotations(entities: &mut Entities) {
+	for (_, transform) in entities.transforms.iter_mut() {
+		transform.rotation = transform.rotation.rem_euclid(std::f32::consts::TAU);
+	}
+}
@@ -0,0 +1,136 @@
+use serde::Deserialize;
+use serde::Serialize;
+use ultraviolet::Vec2;
+
+use crate::world::polygon_contains;
+use crate::world::Entities;
+use crate::world::EntityId;
+
+/// A region of the map that notices entities coming and going, and runs some actions when they do.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Trigger {
+	pub name: String,
+	pub shape: TriggerShape,
+	/// Stop doing anything after the first time something enters.
+	#[serde(default)]
+	pub once: bool,
+	/// Only entities with this tag set the trigger off.
+	#[serde(default)]
+	pub requires_tag: Option<String>,
+	#[serde(default)]
+	pub on_enter: Vec<TriggerAction>,
+	/// Runs every tick something is inside.
+	#[serde(default)]
+	pub on_stay: Vec<TriggerAction>,
+	#[serde(default)]
+	pub on_leave: Vec<TriggerAction>,
+
+	//State, which isn't written in map files, but does get saved
+	#[serde(default)]
+	pub inside: Vec<EntityId>,
+	#[serde(default)]
+	pub spent: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum TriggerShape {
+	Circle {
+		#[serde(with = "crate::util::NotVec2")]
+		center: Vec2,
+		radius: f32,
+	},
+	Polygon(#[serde(with = "crate::util::vec_of_vec2")] Vec<Vec2>),
+}
+
+impl TriggerShape {
+	pub fn contains(&self, point: Vec2) -> bool {
+		match self {
+			TriggerShape::Circle { center, radius } => (point - *center).mag_sq() <= radius * radius,
+			TriggerShape::Polygon(points) => polygon_contains(points, point),
+		}
+	}
+}
+
+/// Things a trigger can do, so level scripting doesn't need any Rust.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum TriggerAction {
+	/// Remember where the entity is, so `Respawn` can put it back here.
+	Checkpoint,
+	/// Send the entity back to the last checkpoint.
+	Respawn,
+	/// Show some text to the player.
+	Message(String),
+	/// Remove every entity with this tag.
+	OpenDoor(String),
+	/// Fire a `GameEvent::Named` at the entity's position, e.g. to play a sound.
+	Event(String),
+	/// Start a timer with this name, running for this many ticks.
+	StartTimer(String, u64),
+	/// Blow a hole this big in the world lines around the entity.
+	Crater(f32),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TriggerPhase {
+	Enter,
+	Stay,
+	Leave,
+}
+
+/// One trigger noticing one entity.
+pub struct TriggerFired {
+	pub trigger: usize,
+	pub phase: TriggerPhase,
+	pub entity: EntityId,
+}
+
+impl Trigger {
+	pub fn actions(&self, phase: TriggerPhase) -> &[TriggerAction] {
+		match phase {
+			TriggerPhase::Enter => &self.on_enter,
+			TriggerPhase::Stay => &self.on_stay,
+			TriggerPhase::Leave => &self.on_leave,
+		}
+	}
+}
+
+/// Checks every trigger against every entity, and reports what went in, stayed in, and came out since last tick.
+pub fn update_triggers(triggers: &mut [Trigger], entities: &Entities) -> Vec<TriggerFired> {
+	let mut fired = Vec::new();
+
+	for (index, trigger) in triggers.iter_mut().enumerate() {
+		if trigger.spent {
+			continue;
+		}
+
+		let now_inside: Vec<EntityId> = entities
+			.transforms
+			.iter()
+			.filter(|(id, _)| match &trigger.requires_tag {
+				Some(tag) => entities.has_tag(*id, tag),
+				None => true,
+			})
+			.filter(|(_, transform)| trigger.shape.contains(transform.position))
+			.map(|(id, _)| id)
+			.collect();
+
+		//things that despawned while inside count as leaving too
+		for &entity in trigger.inside.iter().filter(|id| !now_inside.contains(id)) {
+			fired.push(TriggerFired { trigger: index, phase: TriggerPhase::Leave, entity });
+		}
+
+		for &entity in &now_inside {
+			let phase = if trigger.inside.contains(&entity) { TriggerPhase::Stay } else { TriggerPhase::Enter };
+			fired.push(TriggerFired { trigger: index, phase, entity });
+
+			if phase == TriggerPhase::Enter && trigger.once {
+				trigger.spent = true;
+				break;
+			}
+		}
+
+		trigger.inside = if trigger.spent { Vec::new() } else { now_inside };
+	}
+
+	fired
+}